@@ -1,28 +1,130 @@
 use anyhow::Result;
 use chrono::{NaiveDate, Utc, Duration};
 use rust_decimal::Decimal;
-use rust_decimal::prelude::FromPrimitive;
-use std::collections::{HashMap, HashSet};
-use tracing::{info, error};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use anyhow::anyhow;
+use tracing::{info, warn, error};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::database::Database;
-use crate::prices::{PriceFetcher, CurrencyConverter};
-use crate::portfolio_stats::calculate_portfolio_stats;
+use crate::database::{Database, PortfolioDay};
+use crate::holdings::Position;
+use crate::reconciliation::find_oversold;
+use crate::models::{AccountType, CashRecord, TradingRecord, TransactionKind};
+use crate::quote_unit::detect_quote_unit;
+use crate::mapping_check::verify_all_mappings;
+use crate::manual_prices::{merge_manual_prices, series_currency, single_currency_series, ManualPrice};
+use crate::price_fetch::{fetch_all, FetchConfig, FetchStatus};
+use crate::price_source::PriceSource;
+use crate::prices::CurrencyConverter;
+use crate::trade_prices::{trade_price_series, ValuationSource};
+use crate::cash_activity::income_for_security;
+use crate::cash_balance::daily_cash_balances;
+use crate::data_quality::{assess_series, lookup_price, PriceLookup, QualityConfig};
+use crate::statement_parser::STATEMENT_CURRENCY;
+use crate::portfolio_stats::{calculate_holding_stats, calculate_portfolio_stats};
+use crate::period_returns::calculate_period_returns;
+use crate::risk::{calculate_risk_metrics, risk_free_rate_from_env};
 
-pub async fn precompute_portfolio_data(db: Arc<Database>) -> Result<()> {
+/// Keeps precomputes single-flight: at most one runs at a time.
+#[derive(Default)]
+pub struct PrecomputeRunner {
+    running: AtomicBool,
+    pending: AtomicBool,
+}
+
+impl PrecomputeRunner {
+    /// Starts a run after stored data changed. When one is already running, it goes again
+    /// once finished so the change is not missed.
+    pub fn request(self: &Arc<Self>, db: &Arc<Database>, price_source: &Arc<dyn PriceSource>) {
+        self.pending.store(true, Ordering::SeqCst);
+        self.spawn(db, price_source);
+    }
+
+    /// Starts a run to bring stale data up to date, unless one is already running.
+    pub fn start_if_idle(self: &Arc<Self>, db: &Arc<Database>, price_source: &Arc<dyn PriceSource>) {
+        if !self.running.load(Ordering::SeqCst) {
+            self.request(db, price_source);
+        }
+    }
+
+    fn spawn(self: &Arc<Self>, db: &Arc<Database>, price_source: &Arc<dyn PriceSource>) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let runner = Arc::clone(self);
+        let db = Arc::clone(db);
+        let price_source = Arc::clone(price_source);
+        tokio::spawn(async move {
+            loop {
+                while runner.pending.swap(false, Ordering::SeqCst) {
+                    if let Err(e) = precompute_portfolio_data(Arc::clone(&db), Arc::clone(&price_source)).await {
+                        error!("Background precomputation failed: {}", e);
+                    }
+                }
+                runner.running.store(false, Ordering::SeqCst);
+                // A request may have arrived between the last check and releasing the flag
+                if !runner.pending.load(Ordering::SeqCst) || runner.running.swap(true, Ordering::SeqCst) {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+pub async fn precompute_portfolio_data(db: Arc<Database>, price_source: Arc<dyn PriceSource>) -> Result<()> {
     // 1. Initial status
     let status_id = db.update_precompute_status("in_progress", None, None).await?;
     info!("Starting background precomputation (status_id: {})", status_id);
 
+    // A run left in progress is never retried, so every failure from here on is recorded
+    let result = run_precompute(&db, price_source).await;
+    if let Err(ref e) = result {
+        error!("Precomputation failed: {}", e);
+        db.update_precompute_status("failed", None, Some(&e.to_string())).await?;
+    }
+    result
+}
+
+async fn run_precompute(db: &Database, price_source: Arc<dyn PriceSource>) -> Result<()> {
     // 2. Load basic data from DB
-    let trades = db.load_trades().await?;
-    let external_cfs = db.get_external_cash_flows().await?;
+    let mut trades = db.load_trades().await?;
+    let cash_records = db.load_cash_flows().await?;
+    // Deposits, withdrawals and transfers, i.e. the flows that count as contributions
+    let mut external_cfs: Vec<(AccountType, NaiveDate, Decimal)> = cash_records.iter()
+        .filter(|r| r.kind.is_external())
+        .map(|r| (r.account_type, r.date, r.net_flow))
+        .collect();
+    external_cfs.sort_by_key(|(_, date, _)| *date);
+    let manual_prices = db.load_manual_prices(None).await?;
+    let quote_units = db.get_quote_units().await?;
 
     if trades.is_empty() {
+        db.update_precompute_status("completed", None, None).await?;
         return Ok(());
     }
 
+    // Securities without a ticker can still be valued from manual prices keyed by ISIN
+    let mut manual_by_key: HashMap<&str, Vec<&ManualPrice>> = HashMap::new();
+    for m in &manual_prices {
+        manual_by_key.entry(m.key.as_str()).or_default().push(m);
+    }
+    let mut manual_only = HashSet::new();
+    for t in &mut trades {
+        if t.ticker.is_none() && manual_by_key.contains_key(t.security_isin.as_str()) {
+            t.ticker = Some(t.security_isin.clone());
+            manual_only.insert(t.security_isin.clone());
+        }
+    }
+    // Unmapped securities fall back to their own trade prices rather than being left out
+    let mut unmapped = HashSet::new();
+    for t in &mut trades {
+        if t.ticker.is_none() {
+            t.ticker = Some(t.security_isin.clone());
+            unmapped.insert(t.security_isin.clone());
+        }
+    }
+
     // 3. Identify unique tickers and date range
     let mut tickers = HashSet::new();
     let mut min_date = trades[0].trade_date_time.date();
@@ -37,60 +139,177 @@ pub async fn precompute_portfolio_data(db: Arc<Database>) -> Result<()> {
         }
     }
 
-    for (cf_date, _) in &external_cfs {
+    for (_, cf_date, _) in &external_cfs {
         if *cf_date < min_date {
             min_date = *cf_date;
         }
     }
 
     // 4. Fetch Prices and FX asynchronously
-    let price_fetcher = PriceFetcher::new();
-    let currency_converter = CurrencyConverter::new();
+    let currency_converter = CurrencyConverter::from_env()?;
+    let base_currency = currency_converter.base_currency().to_string();
     
     let mut raw_prices: HashMap<String, HashMap<NaiveDate, Decimal>> = HashMap::new();
     let mut ticker_currencies: HashMap<String, String> = HashMap::new();
+    let mut valuation_sources: BTreeMap<String, ValuationSource> = BTreeMap::new();
     
-    // Add FX tickers to fetch
-    let mut currencies_needed = HashSet::new();
-    let tickers_to_fetch: Vec<String> = tickers.iter().cloned().collect();
+    // Add FX tickers to fetch; statement amounts need converting too unless they are already in the base currency
+    let mut fx_needed: HashSet<String> = currency_converter.legs(STATEMENT_CURRENCY)
+        .into_iter()
+        .map(|leg| leg.ticker)
+        .collect();
+    let mut tickers_to_fetch: Vec<String> = tickers.iter().cloned().collect();
+    tickers_to_fetch.sort();
+
+    let fetch_config = FetchConfig::from_env();
+    let provider_tickers: Vec<String> = tickers_to_fetch.iter()
+        .filter(|t| !manual_only.contains(*t) && !unmapped.contains(*t))
+        .cloned()
+        .collect();
+    info!("Fetching prices for {} tickers", provider_tickers.len());
+    let (mut fetched, mut fetch_outcomes) = fetch_all(
+        db, price_source.as_ref(), &provider_tickers, min_date - Duration::days(7), max_date, &fetch_config,
+    ).await;
 
     for ticker in &tickers_to_fetch {
-        info!("Fetching prices for {}", ticker);
-        match price_fetcher.get_historical_prices(ticker, min_date - Duration::days(7), max_date).await {
-            Ok(prices) => {
-                info!("Fetched {} prices for {}", prices.len(), ticker);
-                let mut p_map = HashMap::new();
-                let mut detected_currency = "GBP".to_string();
-                for (d, p, c) in prices {
-                    p_map.insert(d, p);
-                    detected_currency = c;
-                }
-                raw_prices.insert(ticker.clone(), p_map);
-                ticker_currencies.insert(ticker.clone(), detected_currency.clone());
-                
-                // If it's not a GBP/GBp price, we need an FX rate
-                if detected_currency != "GBP" && detected_currency != "GBp" {
-                    if let Some(fx) = currency_converter.get_fx_ticker(&detected_currency) {
-                        currencies_needed.insert(fx);
+        // ISIN entries first so that entries keyed by the ticker itself win on the same date
+        let isins: HashSet<&str> = trades.iter()
+            .filter(|t| t.ticker.as_deref() == Some(ticker.as_str()) && t.security_isin != *ticker)
+            .map(|t| t.security_isin.as_str())
+            .collect();
+        let manual: Vec<&ManualPrice> = isins.iter()
+            .copied()
+            .chain(std::iter::once(ticker.as_str()))
+            .filter_map(|key| manual_by_key.get(key))
+            .flatten()
+            .copied()
+            .collect();
+
+        let mut prices = match fetched.remove(ticker) {
+            Some(prices) => prices,
+            None if manual_only.contains(ticker) || unmapped.contains(ticker) => Vec::new(),
+            None if !manual.is_empty() => {
+                warn!("Failed to fetch prices for {}, using manual prices only", ticker);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        if prices.is_empty() && manual.is_empty() {
+            let series = trade_price_series(trades.iter().filter(|t| t.ticker.as_deref() == Some(ticker.as_str())), max_date);
+            if series.is_empty() {
+                error!("No prices for {}; its holdings are valued at zero", ticker);
+                continue;
+            }
+            warn!("No provider or manual prices for {}; valuing it approximately from trade prices", ticker);
+            raw_prices.insert(ticker.clone(), series.into_iter().collect());
+            ticker_currencies.insert(ticker.clone(), STATEMENT_CURRENCY.to_string());
+            valuation_sources.insert(ticker.clone(), ValuationSource::TradePrice);
+            continue;
+        }
+        let source = if prices.is_empty() { ValuationSource::Manual } else { ValuationSource::Provider };
+        valuation_sources.insert(ticker.clone(), source);
+        if !manual.is_empty() {
+            info!("Applying {} manual prices to {}", manual.len(), ticker);
+        }
+
+        // Provider labels are unreliable for pence, so bars take the unit from the mapping
+        if !prices.is_empty() {
+            let known = isins.iter().find_map(|isin| quote_units.get(*isin)).cloned();
+            let unit = match known {
+                Some(unit) => Some(unit),
+                None => {
+                    let unit = detect_ticker_quote_unit(price_source.as_ref(), ticker, &prices, &trades).await;
+                    if let Some(ref unit) = unit {
+                        info!("Detected quote unit {} for {}", unit, ticker);
+                        for isin in &isins {
+                            db.set_quote_unit(isin, unit).await?;
+                        }
                     }
+                    unit
+                }
+            };
+            if let Some(unit) = unit {
+                for bar in &mut prices {
+                    bar.2 = unit.clone();
                 }
-            }
-            Err(e) => {
-                error!("Failed to fetch prices for {}: {}", ticker, e);
             }
         }
+
+        let detected_currency = series_currency(&prices, &manual);
+        let (series, dropped) = single_currency_series(merge_manual_prices(prices, &manual), &detected_currency);
+        if dropped > 0 {
+            warn!("Dropped {} manual prices for {} not quoted in {}", dropped, ticker, detected_currency);
+        }
+        raw_prices.insert(ticker.clone(), series.into_iter().collect());
+        ticker_currencies.insert(ticker.clone(), detected_currency.clone());
+
+        fx_needed.extend(currency_converter.legs(&detected_currency).into_iter().map(|leg| leg.ticker));
     }
     
     // Fetch any newly discovered FX tickers
-    for fx in currencies_needed {
-        if raw_prices.contains_key(&fx) { continue; }
-        info!("Fetching FX rates for {}", fx);
-        if let Ok(prices) = price_fetcher.get_historical_prices(&fx, min_date - Duration::days(7), max_date).await {
-            let mut p_map = HashMap::new();
-            for (d, p, _) in prices {
-                p_map.insert(d, p);
+    let mut fx_tickers: Vec<String> = fx_needed.into_iter()
+        .filter(|fx| !raw_prices.contains_key(fx))
+        .collect();
+    fx_tickers.sort();
+    info!("Fetching FX rates for {:?}", fx_tickers);
+    let (fx_prices, fx_outcomes) = fetch_all(
+        db, price_source.as_ref(), &fx_tickers, min_date - Duration::days(7), max_date, &fetch_config,
+    ).await;
+    for (fx, prices) in fx_prices {
+        raw_prices.insert(fx, prices.into_iter().map(|(d, p, _)| (d, p)).collect());
+    }
+    fetch_outcomes.extend(fx_outcomes);
+    for outcome in fetch_outcomes.iter().filter(|o| o.status != FetchStatus::Ok) {
+        error!("Price fetch for {} ended {:?} after {} attempt(s): {}", outcome.ticker, outcome.status, outcome.attempts, outcome.error.as_deref().unwrap_or(""));
+    }
+    db.save_fetch_outcomes(&fetch_outcomes).await?;
+    db.save_valuation_sources(&valuation_sources).await?;
+
+    let to_base = |amount: Decimal, currency: &str, date: NaiveDate| {
+        currency_converter.convert(amount, currency, |leg| {
+            let r = get_price_with_fallback(&raw_prices, &leg.ticker, date).price;
+            if r.is_zero() { None } else { Some(r) }
+        })
+    };
+
+    // Contributions are only meaningful in the base currency, so a missing rate stops the
+    // run; converting them all before anything is cleared keeps the last good results
+    for (_, date, flow) in &mut external_cfs {
+        *flow = to_base(*flow, STATEMENT_CURRENCY, *date)
+            .map_err(|e| anyhow!("Cannot convert {} cash flows to {}: {}", STATEMENT_CURRENCY, base_currency, e))?;
+    }
+    // Trade values in the base currency, aligned with `trades`
+    let mut trade_values = Vec::with_capacity(trades.len());
+    for t in &trades {
+        let value = to_base(t.total_trade_value, STATEMENT_CURRENCY, t.trade_date_time.date())
+            .map_err(|e| anyhow!("Cannot convert {} trades to {}: {}", STATEMENT_CURRENCY, base_currency, e))?;
+        trade_values.push(value);
+    }
+
+    // Uninvested cash per account in the base currency. A day without a usable rate is
+    // left out of the value rather than failing the run, as cash is not a contribution
+    let dates: Vec<NaiveDate> = min_date.iter_days().take_while(|&d| d <= max_date).collect();
+    let mut cash_balances = daily_cash_balances(&cash_records, &trades, &dates);
+    for (account, balances) in &mut cash_balances {
+        let mut skipped = 0;
+        for (balance, &date) in balances.iter_mut().zip(&dates) {
+            if balance.is_zero() {
+                continue;
+            }
+            match to_base(*balance, STATEMENT_CURRENCY, date) {
+                Ok(converted) => *balance = converted,
+                Err(e) => {
+                    if skipped == 0 {
+                        warn!("Cannot convert {:?} cash on {} to {}: {}", account, date, base_currency, e);
+                    }
+                    skipped += 1;
+                    *balance = Decimal::ZERO;
+                }
             }
-            raw_prices.insert(fx, p_map);
+        }
+        if skipped > 0 {
+            warn!("Left {:?} cash out of the value on {} day(s) without an FX rate", account, skipped);
         }
     }
 
@@ -100,56 +319,181 @@ pub async fn precompute_portfolio_data(db: Arc<Database>) -> Result<()> {
     db.clear_precomputed_data().await?;
 
     // Process each date and ticker
-    let dates: Vec<NaiveDate> = min_date.iter_days().take_while(|&d| d <= max_date).collect();
-    let mut daily_ticker_values: HashMap<String, Vec<Decimal>> = HashMap::new();
-    let mut total_daily_values: Vec<Decimal> = Vec::new();
-
-    for ticker in &tickers {
-        daily_ticker_values.insert(ticker.clone(), vec![Decimal::ZERO; dates.len()]);
-    }
-
     // Pre-calculate converted prices and save them
     let mut converted_prices: HashMap<String, HashMap<NaiveDate, Decimal>> = HashMap::new();
     for ticker in &tickers {
-        let reported_currency = ticker_currencies.get(ticker).map(|s| s.as_str()).unwrap_or("GBP");
-        let fx_ticker = currency_converter.get_fx_ticker(reported_currency);
+        let reported_currency = ticker_currencies.get(ticker).map(|s| s.as_str()).unwrap_or(STATEMENT_CURRENCY);
         
         let mut ticker_conv = HashMap::new();
+        let mut saved = Vec::new();
+        // A raw price is never used as if it were in the base currency; without a rate the
+        // last converted price carries forward
+        let mut last_converted = Decimal::ZERO;
+        let mut conversion_failed = false;
         for &date in &dates {
-            let price = get_price_with_fallback(&raw_prices, ticker, date);
-            let fx_rate = fx_ticker.as_ref().and_then(|fx| {
-                let r = get_price_with_fallback(&raw_prices, fx, date);
-                if r.is_zero() { None } else { Some(r) }
-            });
-            
-            let converted = match currency_converter.convert_to_gbp(price, reported_currency, date, fx_rate).await {
-                Ok(c) => c,
+            let lookup = get_price_with_fallback(&raw_prices, ticker, date);
+            let price = lookup.price;
+            if price.is_zero() {
+                ticker_conv.insert(date, Decimal::ZERO);
+                continue;
+            }
+
+            match to_base(price, reported_currency, date) {
+                Ok(converted) => {
+                    last_converted = converted;
+                    saved.push((date, lookup, converted));
+                }
                 Err(e) => {
-                    if !price.is_zero() {
-                        error!("Conversion failed for {} on {}: {}. Using raw price.", ticker, date, e);
+                    if !conversion_failed {
+                        error!("Conversion failed for {} ({}) on {}: {}", ticker, reported_currency, date, e);
+                        conversion_failed = true;
                     }
-                    price
                 }
-            };
-            ticker_conv.insert(date, converted);
-            
-            if !price.is_zero() {
-                db.save_precomputed_ticker_price(ticker, date, reported_currency, price, converted).await?;
             }
+            ticker_conv.insert(date, last_converted);
         }
+        db.save_precomputed_ticker_prices(ticker, reported_currency, &saved).await?;
         converted_prices.insert(ticker.clone(), ticker_conv);
     }
 
+    // Summarise how well each series covered the days it was needed
+    let quality_config = QualityConfig::from_env();
+    let mut quality = Vec::new();
+    for ticker in &tickers_to_fetch {
+        let Some(first_trade) = trades.iter()
+            .filter(|t| t.ticker.as_deref() == Some(ticker.as_str()))
+            .map(|t| t.trade_date_time.date())
+            .min()
+        else {
+            continue;
+        };
+        let needed: Vec<NaiveDate> = dates.iter().copied().filter(|d| *d >= first_trade).collect();
+        let mut q = assess_series(ticker, raw_prices.get(ticker), &needed, &quality_config);
+        q.valuation_source = valuation_sources.get(ticker).copied();
+        quality.push(q);
+    }
+    for fx in &fx_tickers {
+        let mut q = assess_series(fx, raw_prices.get(fx), &dates, &quality_config);
+        q.is_fx = true;
+        quality.push(q);
+    }
+    let flagged = quality.iter().filter(|q| q.has_issues()).count();
+    if flagged > 0 {
+        warn!("{} of {} price series have gaps, stale spans or jumps", flagged, quality.len());
+    }
+    db.save_data_quality(&quality).await?;
+
+    // Security names help match dividend rows to holdings
+    let isins: HashSet<&str> = trades.iter().map(|t| t.security_isin.as_str()).collect();
+    let mut security_names = HashMap::new();
+    for isin in isins {
+        if let Some(name) = db.get_security_name_for_isin(isin).await? {
+            security_names.insert(isin.to_string(), name);
+        }
+    }
+
+    let context = ValuationContext {
+        dates: &dates,
+        converted_prices: &converted_prices,
+        cash_balances: &cash_balances,
+        to_base: &to_base,
+        base_currency: &base_currency,
+        security_names: &security_names,
+        risk_free_rate: risk_free_rate_from_env(),
+    };
+
+    // All accounts combined, then each account on its own
+    let accounts: BTreeSet<AccountType> = trades.iter().map(|t| t.account_type)
+        .chain(cash_records.iter().map(|r| r.account_type))
+        .collect();
+    // Every view replays the same trades, so oversold positions are reported once here
+    for &account in &accounts {
+        let account_trades: Vec<&TradingRecord> = trades.iter().filter(|t| t.account_type == account).collect();
+        for issue in find_oversold(&account_trades) {
+            warn!(
+                "{} of {} {} in {} on {} leaves {} held",
+                issue.transaction_type, issue.quantity, issue.isin, account, issue.trade_date_time, issue.held_after
+            );
+        }
+    }
+    for account in std::iter::once(None).chain(accounts.into_iter().map(Some)) {
+        let in_view = |a: AccountType| account.is_none_or(|v| v == a);
+        let (view_trades, view_trade_values): (Vec<TradingRecord>, Vec<Decimal>) = trades.iter()
+            .zip(&trade_values)
+            .filter(|(t, _)| in_view(t.account_type))
+            .map(|(t, v)| (t.clone(), *v))
+            .unzip();
+        let view_cash: Vec<CashRecord> = cash_records.iter().filter(|r| in_view(r.account_type)).cloned().collect();
+        let view_external: Vec<(NaiveDate, Decimal)> = external_cfs.iter()
+            .filter(|(a, _, _)| in_view(*a))
+            .map(|(_, d, f)| (*d, *f))
+            .collect();
+        compute_view(db, account, &view_trades, &view_trade_values, &view_cash, &view_external, &context).await?;
+    }
+
+    // Prices are cached by now, so checking the mappings costs no provider calls
+    if let Err(e) = verify_all_mappings(db, price_source.as_ref()).await {
+        warn!("Mapping verification failed: {}", e);
+    }
+
+    db.update_precompute_status("completed", None, None).await?;
+    info!("Precomputation completed successfully");
+
+    Ok(())
+}
+
+/// Everything the per-account views share: converted prices and the base currency.
+struct ValuationContext<'a> {
+    dates: &'a [NaiveDate],
+    converted_prices: &'a HashMap<String, HashMap<NaiveDate, Decimal>>,
+    /// Each account's cash on each of `dates`, already in the base currency
+    cash_balances: &'a BTreeMap<AccountType, Vec<Decimal>>,
+    to_base: &'a (dyn Fn(Decimal, &str, NaiveDate) -> Result<Decimal> + Sync),
+    base_currency: &'a str,
+    security_names: &'a HashMap<String, String>,
+    /// Annual, for Sharpe and Sortino
+    risk_free_rate: f64,
+}
+
+/// Values the holdings and cash in `trades` and `cash_records` and saves the daily series,
+/// contributions and metrics under `account` (`None` for all accounts combined).
+///
+/// `trade_values` are the trades' values in the base currency, in the same order.
+async fn compute_view(
+    db: &Database,
+    account: Option<AccountType>,
+    trades: &[TradingRecord],
+    trade_values: &[Decimal],
+    cash_records: &[CashRecord],
+    external_cfs: &[(NaiveDate, Decimal)],
+    ctx: &ValuationContext<'_>,
+) -> Result<()> {
+    let dates = ctx.dates;
+    let to_base = ctx.to_base;
+    let mut tickers: Vec<String> = trades.iter()
+        .filter_map(|t| t.ticker.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    tickers.sort();
+
+    let mut daily_ticker_values: HashMap<String, Vec<Decimal>> = HashMap::new();
+    for ticker in &tickers {
+        daily_ticker_values.insert(ticker.clone(), vec![Decimal::ZERO; dates.len()]);
+    }
+    let mut total_daily_values: Vec<Decimal> = Vec::new();
+    let mut portfolio_days = Vec::with_capacity(dates.len());
+
     // Simulate Holdings
-    let mut sorted_trades = trades.clone();
+    let mut sorted_trades = trades.to_vec();
     sorted_trades.sort_by_key(|t| t.trade_date_time);
-    let mut current_holdings: HashMap<String, Decimal> = HashMap::new();
+    let mut current_holdings: HashMap<String, Position> = HashMap::new();
     let mut trade_idx = 0;
     let mut total_invested_so_far = Decimal::ZERO;
 
     // Pre-map external cash flows for faster lookup
     let mut external_cfs_map: HashMap<NaiveDate, Decimal> = HashMap::new();
-    for (d, f) in &external_cfs {
+    for (d, f) in external_cfs {
         *external_cfs_map.entry(*d).or_insert(Decimal::ZERO) += *f;
     }
 
@@ -163,102 +507,155 @@ pub async fn precompute_portfolio_data(db: Arc<Database>) -> Result<()> {
             let t = &sorted_trades[trade_idx];
             
             if let Some(ref ticker) = t.ticker {
-                let entry = current_holdings.entry(ticker.clone()).or_insert(Decimal::ZERO);
-                let quantity = t.quantity;
-                let t_type = t.transaction_type.to_uppercase();
-                if t_type.contains("BUY") || t_type.contains("DIVIDEND REINVESTMENT") {
-                    *entry += quantity;
-                } else if t_type.contains("SELL") {
-                    *entry -= quantity;
-                }
+                current_holdings.entry(ticker.clone()).or_default().apply(t);
             }
             trade_idx += 1;
         }
 
         let mut total_val = Decimal::ZERO;
         for ticker in &tickers {
-            let shares = *current_holdings.get(ticker).unwrap_or(&Decimal::ZERO);
+            let shares = current_holdings.get(ticker).map(|p| p.quantity).unwrap_or_default();
             
-            let price = converted_prices.get(ticker).and_then(|m| m.get(&date)).cloned().unwrap_or(Decimal::ZERO);
+            let price = ctx.converted_prices.get(ticker).and_then(|m| m.get(&date)).cloned().unwrap_or(Decimal::ZERO);
             let val = shares * price;
             daily_ticker_values.get_mut(ticker).unwrap()[d_idx] = val;
             total_val += val;
-            
-            // Save value for every ticker on every date to ensure vector alignment in API
-            db.save_precomputed_ticker_daily_value(date, ticker, val).await?;
         }
+
+        // Uninvested cash counts towards the value like any holding
+        let mut cash_by_account = BTreeMap::new();
+        for (cash_account, balances) in ctx.cash_balances {
+            if account.is_none_or(|a| a == *cash_account) {
+                cash_by_account.insert(*cash_account, balances[d_idx]);
+            }
+        }
+        total_val += cash_by_account.values().copied().sum::<Decimal>();
+
         total_daily_values.push(total_val);
-        db.save_precomputed_portfolio_value(date, total_val, total_invested_so_far).await?;
+        portfolio_days.push(PortfolioDay { date, value: total_val, invested: total_invested_so_far, cash_by_account });
     }
+    // Every ticker has a value on every date to keep the vectors aligned in the API
+    db.save_precomputed_ticker_daily_values(account, dates, &daily_ticker_values).await?;
+    db.save_precomputed_portfolio_values(account, &portfolio_days).await?;
 
     // Monthly Contributions
     let mut monthly_net: HashMap<String, Decimal> = HashMap::new();
-    for t in &trades {
+    for (t, value) in trades.iter().zip(trade_values) {
         let month = t.trade_date_time.format("%Y-%m").to_string();
-        let val = t.total_trade_value;
-        let entry = monthly_net.entry(month).or_insert(Decimal::ZERO);
-        let t_type = t.transaction_type.to_uppercase();
-        if t_type.contains("BUY") { *entry += val; } else if t_type.contains("SELL") { *entry -= val; }
+        *monthly_net.entry(month).or_insert(Decimal::ZERO) += t.transaction_type.contribution(*value);
     }
-    for (date, net_flow) in &external_cfs {
+    for (date, net_flow) in external_cfs {
         let month = date.format("%Y-%m").to_string();
         *monthly_net.entry(month).or_insert(Decimal::ZERO) += *net_flow;
     }
-    for (month, val) in monthly_net {
-        db.save_precomputed_monthly_contribution(&month, val).await?;
-    }
+    db.save_precomputed_monthly_contributions(account, &monthly_net).await?;
 
     // Stats
+    let max_date = dates.last().copied().unwrap_or_default();
     let current_value = *total_daily_values.last().unwrap_or(&Decimal::ZERO);
     let mut stats_cfs = Vec::new();
     for (d, f) in external_cfs {
-        stats_cfs.push((d, f, "External".to_string()));
+        stats_cfs.push((*d, *f, "External".to_string()));
     }
-    let stats = calculate_portfolio_stats(&stats_cfs, current_value, max_date, Some((&dates, &total_daily_values)));
-
-    db.save_precomputed_metrics(
-        Decimal::from_f64(stats.irr).unwrap_or_default(),
-        Decimal::from_f64(stats.twr).unwrap_or_default(),
-        stats.total_invested,
-        stats.current_value,
-        stats.profit_loss,
-        stats.return_percentage,
-        &max_date.to_string()
-    ).await?;
+    let stats = calculate_portfolio_stats(&stats_cfs, current_value, max_date, Some((dates, &total_daily_values)), ctx.base_currency);
 
-    db.update_precompute_status("completed", None, None).await?;
-    info!("Precomputation completed successfully");
+    db.save_precomputed_metrics(account, &stats).await?;
 
-    Ok(())
-}
+    let daily_flows: Vec<Decimal> = dates.iter()
+        .map(|d| external_cfs_map.get(d).copied().unwrap_or_default())
+        .collect();
+    let period_returns = calculate_period_returns(dates, &total_daily_values, &daily_flows);
+    db.save_precomputed_period_returns(account, &period_returns).await?;
+    let risk = calculate_risk_metrics(dates, &total_daily_values, &daily_flows, ctx.risk_free_rate);
+    db.save_precomputed_risk_metrics(account, &risk).await?;
 
-fn get_price_with_fallback(raw_prices: &HashMap<String, HashMap<NaiveDate, Decimal>>, ticker: &str, date: NaiveDate) -> Decimal {
-    if let Some(p_map) = raw_prices.get(ticker) {
-        // 1. Try looking backwards (Standard "Last Known Price")
-        let mut check_date = date;
-        for _ in 0..90 {
-            if let Some(&p) = p_map.get(&check_date) {
-                if !p.is_zero() { return p; }
-            }
-            if let Some(prev) = check_date.pred_opt() {
-                check_date = prev;
-            } else {
-                break;
+    // Per-holding returns, using each ticker's own trades as its flows
+    let mut holding_stats = Vec::new();
+    for ticker in &tickers {
+        let ticker_trades: Vec<(&TradingRecord, Decimal)> = trades.iter()
+            .zip(trade_values.iter().copied())
+            .filter(|(t, _)| t.ticker.as_deref() == Some(ticker.as_str()))
+            .collect();
+        let isins: HashSet<&str> = ticker_trades.iter().map(|(t, _)| t.security_isin.as_str()).collect();
+        let mut identifiers: Vec<&str> = vec![ticker.split('.').next().unwrap_or(ticker)];
+        for isin in isins {
+            identifiers.push(isin);
+            if let Some(name) = ctx.security_names.get(isin) {
+                identifiers.push(name);
             }
         }
 
-        // 2. If no backward price found, look forward (Handle "Start of History" gap)
-        let mut check_date = date;
-        for _ in 0..30 {
-            if let Some(&p) = p_map.get(&check_date) {
-                if !p.is_zero() { return p; }
-            }
-            if let Some(next) = check_date.succ_opt() {
-                check_date = next;
-            } else {
-                break;
+        // One holding without a rate for its income should not stop the others
+        let converted: Result<Vec<(NaiveDate, Decimal)>> = income_for_security(cash_records, &identifiers)
+            .into_iter()
+            .map(|(date, amount)| Ok((date, to_base(amount, STATEMENT_CURRENCY, date)?)))
+            .collect();
+        let mut distributions = match converted {
+            Ok(distributions) => distributions,
+            Err(e) => {
+                warn!("Skipping returns for {}: cannot convert its income to {}: {}", ticker, ctx.base_currency, e);
+                continue;
             }
+        };
+        // Reinvestments are buys funded by a dividend; without the dividend's cash row the
+        // dividend is taken to be paid out on the same day, so the two cancel
+        let income_matched = !distributions.is_empty();
+        let mut flows = Vec::new();
+        for &(t, value) in &ticker_trades {
+            let date = t.trade_date_time.date();
+            let flow = match t.transaction_type {
+                TransactionKind::Buy | TransactionKind::TransferIn => value,
+                TransactionKind::Sell | TransactionKind::TransferOut => -value,
+                TransactionKind::Other => continue,
+                TransactionKind::DividendReinvestment => {
+                    if !income_matched {
+                        distributions.push((date, value));
+                    }
+                    value
+                }
+            };
+            flows.push((date, flow));
+        }
+
+        let values = daily_ticker_values.get(ticker).map(Vec::as_slice).unwrap_or_default();
+        holding_stats.push(calculate_holding_stats(ticker, &flows, &distributions, dates, values, ctx.base_currency));
+    }
+    db.save_precomputed_holding_metrics(account, &holding_stats).await?;
+
+    Ok(())
+}
+
+/// Detects the quote unit of `ticker` from provider metadata and its first trade.
+async fn detect_ticker_quote_unit(
+    price_source: &dyn PriceSource,
+    ticker: &str,
+    bars: &[(NaiveDate, Decimal, String)],
+    trades: &[TradingRecord],
+) -> Option<String> {
+    let provider_currency = match price_source.currency(ticker).await {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("No currency metadata for {}: {}", ticker, e);
+            None
         }
     }
-    Decimal::ZERO
+    .or_else(|| bars.last().map(|(_, _, c)| c.clone()));
+
+    // Closest close on or up to a week before the first priced trade
+    let sample = trades.iter()
+        .filter(|t| t.ticker.as_deref() == Some(ticker) && !t.share_price.is_zero())
+        .min_by_key(|t| t.trade_date_time)
+        .and_then(|t| {
+            let date = t.trade_date_time.date();
+            bars.iter()
+                .rev()
+                .find(|(d, _, _)| *d <= date && *d >= date - Duration::days(7))
+                .map(|(_, close, _)| (*close, t.share_price))
+        });
+
+    detect_quote_unit(provider_currency.as_deref(), sample.map(|(c, _)| c), sample.map(|(_, p)| p))
+}
+
+fn get_price_with_fallback(raw_prices: &HashMap<String, HashMap<NaiveDate, Decimal>>, ticker: &str, date: NaiveDate) -> PriceLookup {
+    lookup_price(raw_prices.get(ticker), date)
 }
@@ -0,0 +1,113 @@
+use crate::models::{CashActivityKind, CashRecord};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Default, Serialize)]
+pub struct YearlyCashActivity {
+    pub year: i32,
+    pub dividends: Decimal,
+    pub interest: Decimal,
+    /// Fees paid, as a positive amount
+    pub fees: Decimal,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CashActivitySummary {
+    /// Signed net flow per activity kind
+    pub totals: BTreeMap<CashActivityKind, Decimal>,
+    pub rows: BTreeMap<CashActivityKind, usize>,
+    pub by_year: Vec<YearlyCashActivity>,
+}
+
+/// Totals cash rows by kind, with a calendar-year breakdown of income and fees.
+pub fn summarise_cash_activity(records: &[CashRecord]) -> CashActivitySummary {
+    let mut summary = CashActivitySummary::default();
+    let mut years: BTreeMap<i32, YearlyCashActivity> = BTreeMap::new();
+
+    for record in records {
+        *summary.totals.entry(record.kind).or_insert(Decimal::ZERO) += record.net_flow;
+        *summary.rows.entry(record.kind).or_insert(0) += 1;
+
+        let year = record.date.year();
+        let entry = years.entry(year).or_insert_with(|| YearlyCashActivity { year, ..Default::default() });
+        match record.kind {
+            CashActivityKind::Dividend => entry.dividends += record.net_flow,
+            CashActivityKind::Interest => entry.interest += record.net_flow,
+            CashActivityKind::Fee => entry.fees -= record.net_flow,
+            _ => {}
+        }
+    }
+
+    summary.by_year = years.into_values()
+        .filter(|y| !y.dividends.is_zero() || !y.interest.is_zero() || !y.fees.is_zero())
+        .collect();
+    summary
+}
+
+/// Income rows whose activity names the security, by ticker symbol, ISIN or name.
+///
+/// Symbols and ISINs must match a whole word of the activity ("Dividend: VUSA"); names
+/// with several words match anywhere in it. Matching is case-insensitive.
+pub fn income_for_security(records: &[CashRecord], identifiers: &[&str]) -> Vec<(NaiveDate, Decimal)> {
+    let identifiers: Vec<String> = identifiers.iter()
+        .map(|i| i.trim().to_uppercase())
+        .filter(|i| !i.is_empty())
+        .collect();
+    records.iter()
+        .filter(|r| r.kind.is_income())
+        .filter(|r| {
+            let activity = r.activity.to_uppercase();
+            let words: HashSet<&str> = activity.split(|c: char| !c.is_alphanumeric()).collect();
+            identifiers.iter().any(|id| if id.contains(' ') { activity.contains(id.as_str()) } else { words.contains(id.as_str()) })
+        })
+        .map(|r| (r.date, r.net_flow))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AccountType;
+    use rust_decimal_macros::dec;
+
+    fn row(date: (i32, u32, u32), activity: &str, net_flow: Decimal) -> CashRecord {
+        CashRecord {
+            date: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            activity: activity.to_string(),
+            credit: (net_flow > Decimal::ZERO).then_some(net_flow),
+            debit: (net_flow < Decimal::ZERO).then(|| net_flow.abs()),
+            balance: None,
+            account_type: AccountType::ISA,
+            net_flow,
+            kind: CashActivityKind::classify(activity, net_flow),
+        }
+    }
+
+    #[test]
+    fn test_income_and_fees_by_year() {
+        let records = vec![
+            row((2023, 1, 5), "Payment Received", dec!(1000)),
+            row((2023, 1, 6), "Buy: Vanguard FTSE All-World", dec!(-990)),
+            row((2023, 6, 30), "Dividend: VWRP", dec!(4.20)),
+            row((2023, 12, 31), "Management Fee", dec!(-1.50)),
+            row((2024, 3, 31), "Interest", dec!(0.80)),
+            row((2024, 4, 2), "ISA Transfer In", dec!(5000)),
+        ];
+        assert_eq!(records[1].kind, CashActivityKind::BuySettlement);
+        assert_eq!(records[5].kind, CashActivityKind::Transfer);
+
+        let summary = summarise_cash_activity(&records);
+        assert_eq!(summary.totals[&CashActivityKind::Deposit], dec!(1000));
+        assert_eq!(summary.rows[&CashActivityKind::Fee], 1);
+        assert_eq!(summary.by_year.len(), 2);
+        assert_eq!(summary.by_year[0].dividends, dec!(4.20));
+        assert_eq!(summary.by_year[0].fees, dec!(1.50));
+        assert_eq!(summary.by_year[1].interest, dec!(0.80));
+
+        let income = income_for_security(&records, &["VWRP", "Vanguard FTSE All-World"]);
+        assert_eq!(income, vec![(NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(), dec!(4.20))]);
+        assert!(income_for_security(&records, &["VWR"]).is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::models::{AccountType, CashActivityKind, CashRecord, TradingRecord, TransactionKind};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Cash moved by a trade when it settles: buys and reinvestments spend, sales raise.
+fn settlement_flow(trade: &TradingRecord) -> Decimal {
+    match trade.transaction_type {
+        TransactionKind::Buy | TransactionKind::DividendReinvestment => -trade.total_trade_value,
+        TransactionKind::Sell => trade.total_trade_value,
+        // In-specie transfers move shares, not cash
+        TransactionKind::TransferIn | TransactionKind::TransferOut | TransactionKind::Other => Decimal::ZERO,
+    }
+}
+
+/// End-of-day uninvested cash per account for each of `dates`, in the statement currency.
+///
+/// An account whose cash statement includes trade settlements is replayed from that
+/// statement alone. One without settlement rows takes its deposits, income and fees from the
+/// statement and the settlements from the trades, on their settlement dates. Accounts with no
+/// cash rows at all are left out, as their deposits are unknown.
+pub fn daily_cash_balances(
+    cash: &[CashRecord],
+    trades: &[TradingRecord],
+    dates: &[NaiveDate],
+) -> BTreeMap<AccountType, Vec<Decimal>> {
+    let accounts: BTreeSet<AccountType> = cash.iter().map(|r| r.account_type).collect();
+    let mut balances = BTreeMap::new();
+
+    for account in accounts {
+        let rows: Vec<&CashRecord> = cash.iter().filter(|r| r.account_type == account).collect();
+        let has_settlements = rows.iter().any(|r| {
+            matches!(r.kind, CashActivityKind::BuySettlement | CashActivityKind::SellSettlement)
+        });
+
+        let mut flows: BTreeMap<NaiveDate, Decimal> = BTreeMap::new();
+        for r in &rows {
+            *flows.entry(r.date).or_default() += r.net_flow;
+        }
+        if !has_settlements {
+            for t in trades.iter().filter(|t| t.account_type == account) {
+                *flows.entry(t.settlement_date.date()).or_default() += settlement_flow(t);
+            }
+        }
+
+        let mut flows = flows.into_iter().peekable();
+        let mut balance = Decimal::ZERO;
+        let series = dates.iter()
+            .map(|date| {
+                while let Some((_, flow)) = flows.next_if(|(d, _)| d <= date) {
+                    balance += flow;
+                }
+                balance
+            })
+            .collect();
+        balances.insert(account, series);
+    }
+    balances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn d(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn cash(day: u32, activity: &str, net_flow: Decimal, account_type: AccountType) -> CashRecord {
+        CashRecord {
+            date: d(day),
+            activity: activity.to_string(),
+            credit: (net_flow > Decimal::ZERO).then_some(net_flow),
+            debit: (net_flow < Decimal::ZERO).then(|| net_flow.abs()),
+            balance: None,
+            account_type,
+            net_flow,
+            kind: CashActivityKind::classify(activity, net_flow),
+        }
+    }
+
+    fn buy(day: u32, settles: u32, value: Decimal, account_type: AccountType) -> TradingRecord {
+        TradingRecord {
+            security_isin: "IE00BK5BQT80".to_string(),
+            transaction_type: TransactionKind::Buy,
+            quantity: dec!(1),
+            share_price: value,
+            total_trade_value: value,
+            trade_date_time: d(day).and_hms_opt(10, 0, 0).unwrap(),
+            settlement_date: d(settles).and_hms_opt(0, 0, 0).unwrap(),
+            broker: "Winterflood".to_string(),
+            account_type,
+            ticker: Some("VWRP.L".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_daily_cash_balances() {
+        let records = vec![
+            // ISA statement lists its own settlements
+            cash(2, "Payment Received", dec!(1000), AccountType::ISA),
+            cash(4, "Buy: Vanguard FTSE All-World", dec!(-990), AccountType::ISA),
+            cash(5, "Dividend: VWRP", dec!(4.20), AccountType::ISA),
+            // GIA statement only has the deposit
+            cash(2, "Payment Received", dec!(500), AccountType::GIA),
+        ];
+        let trades = vec![
+            buy(2, 4, dec!(990), AccountType::ISA),
+            buy(3, 5, dec!(450), AccountType::GIA),
+            buy(3, 5, dec!(100), AccountType::SIPP),
+        ];
+        let dates: Vec<NaiveDate> = (1..=6).map(d).collect();
+        let balances = daily_cash_balances(&records, &trades, &dates);
+
+        assert_eq!(balances[&AccountType::ISA], vec![dec!(0), dec!(1000), dec!(1000), dec!(10), dec!(14.20), dec!(14.20)]);
+        assert_eq!(balances[&AccountType::GIA], vec![dec!(0), dec!(500), dec!(500), dec!(500), dec!(50), dec!(50)]);
+        assert!(!balances.contains_key(&AccountType::SIPP));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::price_source::{PriceSource, SymbolMatch};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+struct PriceRow {
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "Close")]
+    close: String,
+    #[serde(rename = "Currency", default)]
+    currency: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SymbolRow {
+    #[serde(rename = "Symbol")]
+    symbol: String,
+    #[serde(rename = "Name", default)]
+    name: Option<String>,
+    #[serde(rename = "ISIN", default)]
+    isin: Option<String>,
+    #[serde(rename = "Exchange", default)]
+    exchange: Option<String>,
+    #[serde(rename = "Kind", default)]
+    kind: Option<String>,
+}
+
+/// Offline prices from a directory of CSV files.
+///
+/// Each symbol has a `<SYMBOL>.csv` with `Date,Close[,Currency]` columns (ISO dates,
+/// currency defaults to GBP). An optional `symbols.csv` with
+/// `Symbol,Name,ISIN,Exchange,Kind` columns backs symbol search.
+pub struct CsvPriceSource {
+    dir: PathBuf,
+}
+
+impl CsvPriceSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    async fn read_rows<T: serde::de::DeserializeOwned>(&self, file: &str) -> Result<Option<Vec<T>>> {
+        let path = self.dir.join(file);
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+        };
+        let mut rdr = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(Cursor::new(content));
+        let rows = rdr.deserialize().collect::<Result<Vec<T>, _>>()
+            .map_err(|e| anyhow!("Invalid price file {}: {}", path.display(), e))?;
+        Ok(Some(rows))
+    }
+
+    async fn price_rows(&self, symbol: &str) -> Result<Option<Vec<PriceRow>>> {
+        // Symbols come from requests, so keep them to a file name inside the directory
+        if symbol.is_empty() || symbol.contains(['/', '\\']) || symbol.contains("..") {
+            return Ok(None);
+        }
+        self.read_rows(&format!("{}.csv", symbol)).await
+    }
+}
+
+#[async_trait]
+impl PriceSource for CsvPriceSource {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    async fn historical_prices(&self, symbol: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<(NaiveDate, Decimal, String)>> {
+        let rows = self.price_rows(symbol).await?
+            .ok_or_else(|| anyhow!("No price file for {} in {}", symbol, self.dir.display()))?;
+
+        let mut prices = Vec::new();
+        for row in rows {
+            let date = NaiveDate::parse_from_str(&row.date, "%Y-%m-%d")
+                .map_err(|_| anyhow!("Invalid date '{}' in prices for {}", row.date, symbol))?;
+            if date < start || date > end {
+                continue;
+            }
+            let close = Decimal::from_str(&row.close)
+                .map_err(|_| anyhow!("Invalid close '{}' in prices for {}", row.close, symbol))?;
+            prices.push((date, close, row.currency.unwrap_or_else(|| "GBP".to_string())));
+        }
+        prices.sort_by_key(|(d, _, _)| *d);
+        Ok(prices)
+    }
+
+    async fn currency(&self, symbol: &str) -> Result<Option<String>> {
+        Ok(self.price_rows(symbol).await?.map(|rows| {
+            rows.into_iter().find_map(|r| r.currency).unwrap_or_else(|| "GBP".to_string())
+        }))
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SymbolMatch>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let Some(symbols) = self.read_rows::<SymbolRow>("symbols.csv").await? else {
+            // Without an index the only searchable thing is the file name itself
+            return Ok(match self.price_rows(query).await? {
+                Some(_) => vec![SymbolMatch { symbol: query.to_string(), name: None, exchange: None, kind: "Equity".to_string() }],
+                None => Vec::new(),
+            });
+        };
+
+        let needle = query.to_lowercase();
+        Ok(symbols.into_iter()
+            .filter(|s| {
+                s.symbol.eq_ignore_ascii_case(query)
+                    || s.isin.as_deref().is_some_and(|i| i.eq_ignore_ascii_case(query))
+                    || s.name.as_deref().is_some_and(|n| n.to_lowercase().contains(&needle))
+            })
+            .map(|s| SymbolMatch {
+                symbol: s.symbol,
+                name: s.name,
+                exchange: s.exchange,
+                kind: s.kind.unwrap_or_else(|| "Equity".to_string()),
+            })
+            .collect())
+    }
+}
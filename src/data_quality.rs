@@ -0,0 +1,261 @@
+use crate::env::parse_var;
+use crate::trade_prices::ValuationSource;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Days a lookup may go back for the last known price
+const MAX_CARRY_FORWARD_DAYS: u32 = 90;
+
+/// Days a lookup may go forward when there is no earlier price, e.g. before a listing
+const MAX_BACK_FILL_DAYS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceKind {
+    Exact,
+    CarriedForward,
+    BackFilled,
+    Missing,
+}
+
+impl PriceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriceKind::Exact => "exact",
+            PriceKind::CarriedForward => "carried_forward",
+            PriceKind::BackFilled => "back_filled",
+            PriceKind::Missing => "missing",
+        }
+    }
+}
+
+/// The price used for a date and how it was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceLookup {
+    /// Zero when missing
+    pub price: Decimal,
+    pub kind: PriceKind,
+    /// Days between the date asked for and the date of the price used
+    pub age_days: u32,
+}
+
+/// Finds the price for `date`: the last known price up to 90 days back, otherwise the first
+/// one up to 30 days ahead.
+pub fn lookup_price(series: Option<&HashMap<NaiveDate, Decimal>>, date: NaiveDate) -> PriceLookup {
+    let missing = PriceLookup { price: Decimal::ZERO, kind: PriceKind::Missing, age_days: 0 };
+    let Some(series) = series else {
+        return missing;
+    };
+    let found = |d: Option<NaiveDate>| d.and_then(|d| series.get(&d)).filter(|p| !p.is_zero()).copied();
+
+    for age in 0..MAX_CARRY_FORWARD_DAYS {
+        if let Some(price) = found(date.checked_sub_days(chrono::Days::new(age.into()))) {
+            let kind = if age == 0 { PriceKind::Exact } else { PriceKind::CarriedForward };
+            return PriceLookup { price, kind, age_days: age };
+        }
+    }
+    for age in 1..MAX_BACK_FILL_DAYS {
+        if let Some(price) = found(date.checked_add_days(chrono::Days::new(age.into()))) {
+            return PriceLookup { price, kind: PriceKind::BackFilled, age_days: age };
+        }
+    }
+    missing
+}
+
+/// Thresholds for flagging stale prices and suspicious moves.
+#[derive(Debug, Clone)]
+pub struct QualityConfig {
+    /// A price older than this many days is stale; long weekends stay under the default
+    pub stale_after_days: u32,
+    /// Relative move between consecutive prices that counts as a jump
+    pub jump_threshold: f64,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self { stale_after_days: 5, jump_threshold: 0.3 }
+    }
+}
+
+impl QualityConfig {
+    /// Reads `STALE_PRICE_DAYS` and `PRICE_JUMP_THRESHOLD` (e.g. 0.3 for 30%).
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            stale_after_days: parse_var("STALE_PRICE_DAYS").unwrap_or(default.stale_after_days),
+            jump_threshold: parse_var("PRICE_JUMP_THRESHOLD").unwrap_or(default.jump_threshold),
+        }
+    }
+}
+
+/// A run of consecutive days with the same problem.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: usize,
+    pub max_age_days: u32,
+}
+
+/// A move between two consecutive prices beyond the jump threshold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jump {
+    pub date: NaiveDate,
+    pub previous_date: NaiveDate,
+    pub previous_price: Decimal,
+    pub price: Decimal,
+    pub change: f64,
+}
+
+/// How well one ticker's prices covered the days it was needed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickerQuality {
+    pub ticker: String,
+    pub is_fx: bool,
+    pub valuation_source: Option<ValuationSource>,
+    pub first_date: Option<NaiveDate>,
+    pub last_date: Option<NaiveDate>,
+    pub exact_days: usize,
+    pub carried_forward_days: usize,
+    pub back_filled_days: usize,
+    pub missing_days: usize,
+    pub max_age_days: u32,
+    /// Days valued on a price older than the stale threshold
+    pub stale_spans: Vec<Span>,
+    /// Days with no price at all
+    pub gaps: Vec<Span>,
+    pub jumps: Vec<Jump>,
+}
+
+impl TickerQuality {
+    pub fn has_issues(&self) -> bool {
+        !self.stale_spans.is_empty() || !self.gaps.is_empty() || !self.jumps.is_empty()
+    }
+}
+
+/// Looks up every date in `dates` and summarises where the prices came from.
+pub fn assess_series(
+    ticker: &str,
+    series: Option<&HashMap<NaiveDate, Decimal>>,
+    dates: &[NaiveDate],
+    config: &QualityConfig,
+) -> TickerQuality {
+    let mut quality = TickerQuality {
+        ticker: ticker.to_string(),
+        is_fx: false,
+        valuation_source: None,
+        first_date: dates.first().copied(),
+        last_date: dates.last().copied(),
+        exact_days: 0,
+        carried_forward_days: 0,
+        back_filled_days: 0,
+        missing_days: 0,
+        max_age_days: 0,
+        stale_spans: Vec::new(),
+        gaps: Vec::new(),
+        jumps: Vec::new(),
+    };
+
+    let mut stale: Option<Span> = None;
+    let mut gap: Option<Span> = None;
+    for &date in dates {
+        let lookup = lookup_price(series, date);
+        match lookup.kind {
+            PriceKind::Exact => quality.exact_days += 1,
+            PriceKind::CarriedForward => quality.carried_forward_days += 1,
+            PriceKind::BackFilled => quality.back_filled_days += 1,
+            PriceKind::Missing => quality.missing_days += 1,
+        }
+        quality.max_age_days = quality.max_age_days.max(lookup.age_days);
+
+        let is_stale = lookup.kind != PriceKind::Missing && lookup.age_days > config.stale_after_days;
+        extend_span(&mut stale, &mut quality.stale_spans, is_stale, date, lookup.age_days);
+        extend_span(&mut gap, &mut quality.gaps, lookup.kind == PriceKind::Missing, date, 0);
+    }
+    quality.stale_spans.extend(stale);
+    quality.gaps.extend(gap);
+
+    if let (Some(series), Some(&first), Some(&last)) = (series, dates.first(), dates.last()) {
+        let mut observed: Vec<(NaiveDate, Decimal)> = series.iter()
+            .filter(|(d, p)| **d >= first && **d <= last && !p.is_zero())
+            .map(|(d, p)| (*d, *p))
+            .collect();
+        observed.sort_by_key(|(d, _)| *d);
+        quality.jumps = observed.windows(2)
+            .filter_map(|w| {
+                let ((previous_date, previous_price), (date, price)) = (w[0], w[1]);
+                let change = (price / previous_price).to_f64()? - 1.0;
+                (change.abs() > config.jump_threshold)
+                    .then_some(Jump { date, previous_date, previous_price, price, change })
+            })
+            .collect();
+    }
+
+    quality
+}
+
+/// Adds `date` to the open span when `flagged`, otherwise closes it.
+fn extend_span(open: &mut Option<Span>, closed: &mut Vec<Span>, flagged: bool, date: NaiveDate, age_days: u32) {
+    if !flagged {
+        closed.extend(open.take());
+        return;
+    }
+    match open {
+        Some(span) => {
+            span.end = date;
+            span.days += 1;
+            span.max_age_days = span.max_age_days.max(age_days);
+        }
+        None => *open = Some(Span { start: date, end: date, days: 1, max_age_days: age_days }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    #[test]
+    fn test_lookup_price() {
+        let series: HashMap<NaiveDate, Decimal> = [(d(2024, 1, 10), dec!(100)), (d(2024, 1, 12), dec!(101))].into_iter().collect();
+
+        assert_eq!(lookup_price(Some(&series), d(2024, 1, 12)).kind, PriceKind::Exact);
+        let carried = lookup_price(Some(&series), d(2024, 1, 15));
+        assert_eq!((carried.price, carried.kind, carried.age_days), (dec!(101), PriceKind::CarriedForward, 3));
+        let back = lookup_price(Some(&series), d(2024, 1, 1));
+        assert_eq!((back.price, back.kind, back.age_days), (dec!(100), PriceKind::BackFilled, 9));
+        assert_eq!(lookup_price(Some(&series), d(2024, 6, 1)).kind, PriceKind::Missing);
+        assert_eq!(lookup_price(None, d(2024, 1, 12)).kind, PriceKind::Missing);
+    }
+
+    #[test]
+    fn test_assess_series() {
+        // Prices stop for two weeks and then jump 50%
+        let series: HashMap<NaiveDate, Decimal> = [
+            (d(2024, 1, 1), dec!(100)),
+            (d(2024, 1, 2), dec!(101)),
+            (d(2024, 1, 17), dec!(151.5)),
+        ].into_iter().collect();
+        let dates: Vec<NaiveDate> = d(2024, 1, 1).iter_days().take(20).collect();
+        let quality = assess_series("VWRP.L", Some(&series), &dates, &QualityConfig::default());
+
+        assert_eq!(quality.exact_days, 3);
+        assert_eq!(quality.carried_forward_days, 17);
+        assert_eq!(quality.max_age_days, 14);
+        assert_eq!(quality.stale_spans, vec![Span { start: d(2024, 1, 8), end: d(2024, 1, 16), days: 9, max_age_days: 14 }]);
+        assert!(quality.gaps.is_empty());
+        assert_eq!(quality.jumps.len(), 1);
+        assert_eq!(quality.jumps[0].date, d(2024, 1, 17));
+
+        let none = assess_series("GBPUSD=X", None, &dates, &QualityConfig::default());
+        assert_eq!(none.missing_days, 20);
+        assert_eq!(none.gaps.len(), 1);
+    }
+}
@@ -1,24 +1,73 @@
 use anyhow::Result;
-use mongodb::{Client, Database as MongoDatabase, bson::{doc, Bson}};
-use mongodb::options::{UpdateOptions, FindOptions, IndexOptions};
+use mongodb::{Client, Database as MongoDatabase, bson::{doc, Bson, Document}};
+use mongodb::options::{UpdateOptions, FindOptions, FindOneOptions, IndexOptions};
 use mongodb::IndexModel;
 use futures::stream::StreamExt;
-use crate::models::{TradingRecord, CashRecord};
+use crate::models::{AccountType, CashActivityKind, TradingRecord, TransactionKind, CashRecord};
+use crate::dedup::{MergeSummary, partition_new_records};
+use crate::portfolio_stats::{HoldingStats, PortfolioStats};
+use crate::price_cache::PriceCoverage;
+use crate::price_fetch::{FetchOutcome, FetchStatus};
+use crate::manual_prices::ManualPrice;
+use crate::quote_unit::normalise_currency;
+use crate::mapping_check::MappingVerification;
+use crate::trade_prices::ValuationSource;
+use crate::data_quality::{PriceLookup, TickerQuality};
+use crate::period_returns::PeriodReturn;
+use crate::risk::RiskMetrics;
 use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
 use chrono::{NaiveDate, NaiveDateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
-use tracing::info;
+use tracing::{info, warn};
+
+/// Bumped whenever the meaning of cached bars changes, forcing a full refetch.
+const PRICE_CACHE_VERSION: i32 = 1;
 
 pub struct Database {
     db: MongoDatabase,
 }
 
+/// One day of a precomputed view.
+#[derive(Debug, Clone)]
+pub struct PortfolioDay {
+    pub date: NaiveDate,
+    /// Includes the cash in `cash_by_account`
+    pub value: Decimal,
+    pub invested: Decimal,
+    pub cash_by_account: BTreeMap<AccountType, Decimal>,
+}
+
+/// Key of the precomputed view covering every account.
+pub const ALL_ACCOUNTS: &str = "ALL";
+
+/// Key a precomputed view is stored under; `None` is all accounts combined.
+fn view_key(account: Option<AccountType>) -> &'static str {
+    account.map_or(ALL_ACCOUNTS, |a| a.as_str())
+}
+
+/// Filter on `account_type`; legacy rows stored as "Unknown" match `Other`.
+fn account_filter(account: Option<AccountType>) -> Document {
+    match account {
+        None => doc! {},
+        Some(AccountType::Other) => {
+            let named: Vec<&str> = AccountType::ALL.iter()
+                .filter(|a| **a != AccountType::Other)
+                .map(|a| a.as_str())
+                .collect();
+            doc! { "account_type": { "$nin": named } }
+        }
+        Some(a) => doc! { "account_type": a.as_str() },
+    }
+}
+
 impl Database {
     pub async fn new(uri: &str) -> Result<Self> {
         let client = Client::with_uri_str(uri).await?;
         
         // Extract database name from URI or default to "bot_db"
-        let db_name = if let Some(path) = uri.split('/').last() {
+        let db_name = if let Some(path) = uri.split('/').next_back() {
             if path.is_empty() { "bot_db" } else { path.split('?').next().unwrap_or("bot_db") }
         } else {
             "bot_db"
@@ -41,6 +90,22 @@ impl Database {
                 .build()
         ).await?;
 
+        let coverage_coll = self.db.collection::<Bson>("price_coverage");
+        coverage_coll.create_index(
+            IndexModel::builder()
+                .keys(doc! { "ticker": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build()
+        ).await?;
+
+        let manual_coll = self.db.collection::<Bson>("manual_prices");
+        manual_coll.create_index(
+            IndexModel::builder()
+                .keys(doc! { "key": 1, "date": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build()
+        ).await?;
+
         // isin_to_ticker: isin
         let isin_coll = self.db.collection::<Bson>("isin_to_ticker");
         isin_coll.create_index(
@@ -50,19 +115,22 @@ impl Database {
                 .build()
         ).await?;
 
-        // precomputed tables unique keys
+        // precomputed tables unique keys; views are keyed by account, so indexes from before
+        // that are dropped (a missing index is not an error worth stopping for)
         let coll = self.db.collection::<Bson>("precomputed_portfolio_values");
+        coll.drop_index("date_1").await.ok();
         coll.create_index(
             IndexModel::builder()
-                .keys(doc! { "date": 1 })
+                .keys(doc! { "account": 1, "date": 1 })
                 .options(IndexOptions::builder().unique(true).build())
                 .build()
         ).await?;
 
         let coll = self.db.collection::<Bson>("precomputed_monthly_contributions");
+        coll.drop_index("month_1").await.ok();
         coll.create_index(
             IndexModel::builder()
-                .keys(doc! { "month": 1 })
+                .keys(doc! { "account": 1, "month": 1 })
                 .options(IndexOptions::builder().unique(true).build())
                 .build()
         ).await?;
@@ -76,9 +144,26 @@ impl Database {
         ).await?;
 
         let coll = self.db.collection::<Bson>("precomputed_ticker_daily_values");
+        coll.drop_index("date_1_ticker_1").await.ok();
+        coll.create_index(
+            IndexModel::builder()
+                .keys(doc! { "account": 1, "date": 1, "ticker": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build()
+        ).await?;
+
+        let coll = self.db.collection::<Bson>("precomputed_holding_metrics");
         coll.create_index(
             IndexModel::builder()
-                .keys(doc! { "date": 1, "ticker": 1 })
+                .keys(doc! { "account": 1, "ticker": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build()
+        ).await?;
+
+        let coll = self.db.collection::<Bson>("precomputed_period_returns");
+        coll.create_index(
+            IndexModel::builder()
+                .keys(doc! { "account": 1, "position": 1 })
                 .options(IndexOptions::builder().unique(true).build())
                 .build()
         ).await?;
@@ -86,29 +171,15 @@ impl Database {
         Ok(())
     }
 
+    /// Deposits, withdrawals and transfers in date order, i.e. the flows that count as contributions.
     pub async fn get_external_cash_flows(&self) -> Result<Vec<(NaiveDate, Decimal)>> {
-        let coll = self.db.collection::<mongodb::bson::Document>("cash_flows");
-        let filter = doc! {
-            "$or": [
-                { "activity": { "$regex": "PAYMENT RECEIVED", "$options": "i" } },
-                { "activity": { "$regex": "WITHDRAWAL", "$options": "i" } },
-                { "activity": { "$regex": "ISA TRANSFER IN", "$options": "i" } }
-            ]
-        };
-        let find_options = FindOptions::builder().sort(doc! { "date": 1 }).build();
-        let mut cursor = coll.find(filter).with_options(find_options).await?;
-        
-        let mut results = Vec::new();
-        while let Some(result) = cursor.next().await {
-            let doc = result?;
-            let date_str = doc.get_str("date")?;
-            let net_flow_str = doc.get_str("net_flow")?;
-            results.push((
-                NaiveDate::parse_from_str(date_str, "%Y-%m-%d").unwrap_or_default(),
-                Decimal::from_str(net_flow_str).unwrap_or_default()
-            ));
-        }
-        Ok(results)
+        let mut flows: Vec<(NaiveDate, Decimal)> = self.load_cash_flows().await?
+            .into_iter()
+            .filter(|r| r.kind.is_external())
+            .map(|r| (r.date, r.net_flow))
+            .collect();
+        flows.sort_by_key(|(date, _)| *date);
+        Ok(flows)
     }
 
     pub async fn update_precompute_status(&self, status: &str, total_tickers: Option<usize>, error: Option<&str>) -> Result<String> {
@@ -158,6 +229,57 @@ impl Database {
         }
     }
 
+    /// Records per-ticker fetch results on the latest precompute run.
+    pub async fn save_fetch_outcomes(&self, outcomes: &[FetchOutcome]) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precompute_status");
+        let find_options = FindOptions::builder().sort(doc! { "_id": -1 }).limit(1).build();
+        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let Some(result) = cursor.next().await else {
+            return Ok(());
+        };
+        let id = result?.get_object_id("_id")?;
+        let failed: Vec<&str> = outcomes.iter()
+            .filter(|o| o.status == FetchStatus::Failed)
+            .map(|o| o.ticker.as_str())
+            .collect();
+        coll.update_one(doc! { "_id": id }, doc! { "$set": {
+            "fetch_outcomes": mongodb::bson::to_bson(outcomes)?,
+            "failed_tickers": failed,
+        } }).await?;
+        Ok(())
+    }
+
+    /// Records where each ticker's prices came from on the latest precompute run.
+    pub async fn save_valuation_sources(&self, sources: &std::collections::BTreeMap<String, ValuationSource>) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precompute_status");
+        let find_options = FindOptions::builder().sort(doc! { "_id": -1 }).limit(1).build();
+        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let Some(result) = cursor.next().await else {
+            return Ok(());
+        };
+        let id = result?.get_object_id("_id")?;
+        // Tickers contain dots, so they are stored as values rather than field names
+        let entries: Vec<Document> = sources.iter()
+            .map(|(ticker, source)| Ok(doc! { "ticker": ticker, "source": mongodb::bson::to_bson(source)? }))
+            .collect::<Result<_>>()?;
+        coll.update_one(doc! { "_id": id }, doc! { "$set": { "valuation_sources": entries } }).await?;
+        Ok(())
+    }
+
+    /// Fetch results of the most recent run that recorded any.
+    pub async fn get_last_fetch_outcomes(&self) -> Result<Vec<FetchOutcome>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precompute_status");
+        let find_options = FindOptions::builder().sort(doc! { "_id": -1 }).limit(1).build();
+        let mut cursor = coll.find(doc! { "fetch_outcomes": { "$exists": true } }).with_options(find_options).await?;
+        match cursor.next().await {
+            Some(result) => {
+                let outcomes = result?.get("fetch_outcomes").cloned().unwrap_or(Bson::Array(Vec::new()));
+                Ok(mongodb::bson::from_bson(outcomes)?)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
     pub async fn get_precompute_status(&self) -> Result<serde_json::Value> {
         let coll = self.db.collection::<mongodb::bson::Document>("precompute_status");
         let find_options = FindOptions::builder().sort(doc! { "_id": -1 }).limit(1).build();
@@ -171,6 +293,16 @@ impl Database {
                 "completed_at": doc.get_str("completed_at").ok(),
                 "total_tickers": doc.get_i64("total_tickers").ok(),
                 "last_error": doc.get_str("last_error").ok(),
+                "failed_tickers": doc.get_array("failed_tickers").ok()
+                    .map(|a| a.iter().filter_map(|t| t.as_str()).collect::<Vec<_>>()),
+                "fetch_outcomes": doc.get("fetch_outcomes").cloned()
+                    .and_then(|o| mongodb::bson::from_bson::<Vec<FetchOutcome>>(o).ok()),
+                "valuation_sources": doc.get_array("valuation_sources").ok().map(|entries| {
+                    entries.iter()
+                        .filter_map(|e| e.as_document())
+                        .filter_map(|e| Some((e.get_str("ticker").ok()?.to_string(), serde_json::json!(e.get_str("source").ok()?))))
+                        .collect::<serde_json::Map<_, _>>()
+                }),
                 "has_data": true,
             }))
         } else {
@@ -181,20 +313,38 @@ impl Database {
         }
     }
 
-    pub async fn get_portfolio_values_precomputed(&self) -> Result<Option<serde_json::Value>> {
+    /// The precomputed view for one account, or for all of them with `None`.
+    pub async fn get_portfolio_values_precomputed(&self, account: Option<AccountType>) -> Result<Option<serde_json::Value>> {
+        let view = doc! { "account": view_key(account) };
+
         // Daily values
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_values");
         let find_options = FindOptions::builder().sort(doc! { "date": 1 }).build();
-        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let mut cursor = coll.find(view.clone()).with_options(find_options).await?;
         
         let mut daily_dates = Vec::new();
         let mut daily_values = Vec::new();
         let mut daily_invested = Vec::new();
+        let mut daily_cash = Vec::new();
+        let mut daily_cash_by_account: std::collections::BTreeMap<String, Vec<f64>> = std::collections::BTreeMap::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
+            let idx = daily_dates.len();
             daily_dates.push(doc.get_str("date")?.to_string());
             daily_values.push(doc.get_str("daily_value")?.parse::<f64>().unwrap_or(0.0));
             daily_invested.push(doc.get_str("invested_value").unwrap_or("0").parse::<f64>().unwrap_or(0.0));
+            daily_cash.push(doc.get_str("daily_cash").unwrap_or("0").parse::<f64>().unwrap_or(0.0));
+            if let Ok(accounts) = doc.get_document("cash_by_account") {
+                for (account, balance) in accounts {
+                    // Pad so every account's series lines up with daily_dates
+                    let series = daily_cash_by_account.entry(account.clone()).or_insert_with(|| vec![0.0; idx]);
+                    series.resize(idx, 0.0);
+                    series.push(balance.as_str().and_then(|b| b.parse().ok()).unwrap_or(0.0));
+                }
+            }
+        }
+        for series in daily_cash_by_account.values_mut() {
+            series.resize(daily_dates.len(), 0.0);
         }
 
         if daily_dates.is_empty() {
@@ -204,7 +354,7 @@ impl Database {
         // Monthly contributions
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_monthly_contributions");
         let find_options = FindOptions::builder().sort(doc! { "month": 1 }).build();
-        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let mut cursor = coll.find(view.clone()).with_options(find_options).await?;
         let mut monthly_net = Vec::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
@@ -217,7 +367,7 @@ impl Database {
         // Ticker daily values
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_ticker_daily_values");
         let find_options = FindOptions::builder().sort(doc! { "date": 1, "ticker": 1 }).build();
-        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let mut cursor = coll.find(view.clone()).with_options(find_options).await?;
         let mut daily_ticker_values: std::collections::HashMap<String, Vec<f64>> = std::collections::HashMap::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
@@ -228,7 +378,11 @@ impl Database {
 
         // Metrics
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_metrics");
-        let doc_opt = coll.find_one(doc! { "id": 1 }).await?;
+        let doc_opt = coll.find_one(view).await?;
+        let risk_metrics = match doc_opt.as_ref().and_then(|doc| doc.get_document("risk").ok()) {
+            Some(risk) => Some(mongodb::bson::from_document::<RiskMetrics>(risk.clone())?),
+            None => None,
+        };
         let portfolio_stats = if let Some(doc) = doc_opt {
             serde_json::json!({
                 "irr": doc.get_str("irr")?.parse::<f64>().unwrap_or(0.0),
@@ -238,6 +392,7 @@ impl Database {
                 "profit_loss": doc.get_str("profit_loss")?.parse::<f64>().unwrap_or(0.0),
                 "return_percentage": doc.get_str("return_percentage")?.parse::<f64>().unwrap_or(0.0),
                 "calc_date": doc.get_str("calc_date")?,
+                "currency": doc.get_str("currency").unwrap_or("GBP"),
                 "last_updated": doc.get_str("last_updated")?,
             })
         } else {
@@ -245,16 +400,46 @@ impl Database {
         };
 
         Ok(Some(serde_json::json!({
+            "account": view_key(account),
             "monthly_net": monthly_net,
             "daily_dates": daily_dates,
             "daily_values": daily_values,
             "daily_invested": daily_invested,
+            "daily_cash": daily_cash,
+            "daily_cash_by_account": daily_cash_by_account,
             "daily_ticker_values": daily_ticker_values,
             "portfolio_stats": portfolio_stats,
+            "risk_metrics": risk_metrics,
         })))
     }
 
-    pub async fn get_all_precomputed_data(&self) -> Result<serde_json::Value> {
+    /// One view's daily dates, values and external flows, for series derived from them.
+    ///
+    /// Flows are the day-on-day change in invested capital, which only moves with them.
+    pub async fn get_daily_values_and_flows(&self, account: Option<AccountType>) -> Result<(Vec<NaiveDate>, Vec<Decimal>, Vec<Decimal>)> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_values");
+        let find_options = FindOptions::builder().sort(doc! { "date": 1 }).build();
+        let mut cursor = coll.find(doc! { "account": view_key(account) }).with_options(find_options).await?;
+
+        let mut dates = Vec::new();
+        let mut values = Vec::new();
+        let mut flows = Vec::new();
+        let mut previous_invested = Decimal::ZERO;
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            let invested = Decimal::from_str(doc.get_str("invested_value").unwrap_or("0")).unwrap_or_default();
+            dates.push(NaiveDate::parse_from_str(doc.get_str("date")?, "%Y-%m-%d")?);
+            values.push(Decimal::from_str(doc.get_str("daily_value")?).unwrap_or_default());
+            flows.push(invested - previous_invested);
+            previous_invested = invested;
+        }
+        Ok((dates, values, flows))
+    }
+
+    /// Every precomputed row; prices are shared, the rest come from one account's view.
+    pub async fn get_all_precomputed_data(&self, account: Option<AccountType>) -> Result<serde_json::Value> {
+        let view = doc! { "account": view_key(account) };
+
         // Ticker prices
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_ticker_prices");
         let find_options = FindOptions::builder().sort(doc! { "ticker": 1, "date": 1 }).build();
@@ -262,12 +447,16 @@ impl Database {
         let mut ticker_prices = Vec::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
+            // Rows saved before the base currency became configurable only have the old key
+            let converted = doc.get_str("converted_price").or_else(|_| doc.get_str("converted_price_gbp"))?;
             ticker_prices.push(serde_json::json!({
                 "ticker": doc.get_str("ticker")?,
                 "date": doc.get_str("date")?,
                 "original_currency": doc.get_str("original_currency")?,
                 "original_price": doc.get_str("original_price")?,
-                "converted_price_gbp": doc.get_str("converted_price_gbp")?,
+                "converted_price": converted,
+                // Deprecated: kept for existing consumers, same value as converted_price
+                "converted_price_gbp": converted,
                 "last_updated": doc.get_str("last_updated")?,
             }));
         }
@@ -275,7 +464,7 @@ impl Database {
         // Ticker daily values
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_ticker_daily_values");
         let find_options = FindOptions::builder().sort(doc! { "date": 1, "ticker": 1 }).build();
-        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let mut cursor = coll.find(view.clone()).with_options(find_options).await?;
         let mut ticker_daily_values = Vec::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
@@ -290,7 +479,7 @@ impl Database {
         // Portfolio values
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_values");
         let find_options = FindOptions::builder().sort(doc! { "date": 1 }).build();
-        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let mut cursor = coll.find(view.clone()).with_options(find_options).await?;
         let mut portfolio_values = Vec::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
@@ -304,7 +493,7 @@ impl Database {
         // Monthly contributions
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_monthly_contributions");
         let find_options = FindOptions::builder().sort(doc! { "month": 1 }).build();
-        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+        let mut cursor = coll.find(view.clone()).with_options(find_options).await?;
         let mut monthly_contributions = Vec::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
@@ -317,7 +506,7 @@ impl Database {
 
         // Metrics
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_metrics");
-        let doc_opt = coll.find_one(doc! { "id": 1 }).await?;
+        let doc_opt = coll.find_one(view).await?;
         let metrics = if let Some(doc) = doc_opt {
             serde_json::json!({
                 "irr": doc.get_str("irr")?,
@@ -336,6 +525,7 @@ impl Database {
         let status = self.get_precompute_status().await?;
 
         Ok(serde_json::json!({
+            "account": view_key(account),
             "ticker_prices": ticker_prices,
             "ticker_daily_values": ticker_daily_values,
             "portfolio_values": portfolio_values,
@@ -357,63 +547,182 @@ impl Database {
         self.db.collection::<Bson>("precomputed_ticker_prices").delete_many(doc! {}).await?;
         self.db.collection::<Bson>("precomputed_ticker_daily_values").delete_many(doc! {}).await?;
         self.db.collection::<Bson>("precomputed_portfolio_metrics").delete_many(doc! {}).await?;
+        self.db.collection::<Bson>("precomputed_data_quality").delete_many(doc! {}).await?;
+        self.db.collection::<Bson>("precomputed_holding_metrics").delete_many(doc! {}).await?;
+        self.db.collection::<Bson>("precomputed_period_returns").delete_many(doc! {}).await?;
         Ok(())
     }
 
-    pub async fn save_precomputed_ticker_price(&self, ticker: &str, date: NaiveDate, currency: &str, original: Decimal, converted: Decimal) -> Result<()> {
+    /// Replaces one ticker's daily prices: (date, price as quoted in `currency`, converted).
+    pub async fn save_precomputed_ticker_prices(&self, ticker: &str, currency: &str, prices: &[(NaiveDate, PriceLookup, Decimal)]) -> Result<()> {
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_ticker_prices");
-        let filter = doc! { "ticker": ticker, "date": date.to_string() };
-        let update = doc! {
-            "$set": {
+        coll.delete_many(doc! { "ticker": ticker }).await?;
+        if prices.is_empty() {
+            return Ok(());
+        }
+        let last_updated = Utc::now().to_rfc3339();
+        let docs: Vec<Document> = prices.iter()
+            .map(|(date, original, converted)| doc! {
                 "ticker": ticker,
                 "date": date.to_string(),
                 "original_currency": currency,
-                "original_price": original.to_string(),
+                "original_price": original.price.to_string(),
+                "converted_price": converted.to_string(),
+                // Deprecated alias of converted_price, kept for readers of the old key
                 "converted_price_gbp": converted.to_string(),
-                "last_updated": Utc::now().to_rfc3339(),
-            }
-        };
-        coll.update_one(filter, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
+                "price_kind": original.kind.as_str(),
+                "price_age_days": original.age_days as i64,
+                "last_updated": &last_updated,
+            })
+            .collect();
+        coll.insert_many(docs).await?;
         Ok(())
     }
 
-    pub async fn save_precomputed_portfolio_value(&self, date: NaiveDate, value: Decimal, invested: Decimal) -> Result<()> {
-        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_values");
-        let filter = doc! { "date": date.to_string() };
-        let update = doc! {
-            "$set": {
-                "date": date.to_string(),
-                "daily_value": value.to_string(),
-                "invested_value": invested.to_string(),
-                "last_updated": Utc::now().to_rfc3339(),
+    pub async fn save_data_quality(&self, quality: &[TickerQuality]) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_data_quality");
+        coll.delete_many(doc! {}).await?;
+        if quality.is_empty() {
+            return Ok(());
+        }
+        let generated_at = Utc::now().to_rfc3339();
+        let mut docs = Vec::new();
+        for q in quality {
+            let mut doc = mongodb::bson::to_document(q)?;
+            doc.insert("generated_at", &generated_at);
+            docs.push(doc);
+        }
+        coll.insert_many(docs).await?;
+        Ok(())
+    }
+
+    /// Data quality summaries from the last precompute with the time they were made.
+    pub async fn load_data_quality(&self) -> Result<(Vec<TickerQuality>, Option<String>)> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_data_quality");
+        let find_options = FindOptions::builder().sort(doc! { "is_fx": 1, "ticker": 1 }).build();
+        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+
+        let mut results = Vec::new();
+        let mut generated_at = None;
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            if generated_at.is_none() {
+                generated_at = doc.get_str("generated_at").ok().map(str::to_string);
             }
-        };
-        coll.update_one(filter, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
+            results.push(mongodb::bson::from_document(doc)?);
+        }
+        Ok((results, generated_at))
+    }
+
+    /// Where each day's price for `ticker` came from and how old it was.
+    pub async fn get_price_provenance(&self, ticker: &str) -> Result<Vec<serde_json::Value>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_ticker_prices");
+        let find_options = FindOptions::builder().sort(doc! { "date": 1 }).build();
+        let mut cursor = coll.find(doc! { "ticker": ticker }).with_options(find_options).await?;
+
+        let mut days = Vec::new();
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            days.push(serde_json::json!({
+                "date": doc.get_str("date")?,
+                "price": doc.get_str("original_price")?,
+                "kind": doc.get_str("price_kind").unwrap_or("exact"),
+                "age_days": doc.get_i64("price_age_days").unwrap_or(0),
+            }));
+        }
+        Ok(days)
+    }
+
+    /// Replaces one view's daily values.
+    pub async fn save_precomputed_portfolio_values(&self, account: Option<AccountType>, days: &[PortfolioDay]) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_values");
+        coll.delete_many(doc! { "account": view_key(account) }).await?;
+        if days.is_empty() {
+            return Ok(());
+        }
+        let last_updated = Utc::now().to_rfc3339();
+        let docs: Vec<Document> = days.iter()
+            .map(|day| {
+                let cash: Decimal = day.cash_by_account.values().sum();
+                let mut by_account = Document::new();
+                for (account, balance) in &day.cash_by_account {
+                    by_account.insert(account.as_str(), balance.to_string());
+                }
+                doc! {
+                    "account": view_key(account),
+                    "date": day.date.to_string(),
+                    "daily_value": day.value.to_string(),
+                    "invested_value": day.invested.to_string(),
+                    "daily_cash": cash.to_string(),
+                    "cash_by_account": by_account,
+                    "last_updated": &last_updated,
+                }
+            })
+            .collect();
+        coll.insert_many(docs).await?;
         Ok(())
     }
 
-    pub async fn save_precomputed_ticker_daily_value(&self, date: NaiveDate, ticker: &str, value: Decimal) -> Result<()> {
+    /// Replaces one view's per-ticker daily values, each series aligned with `dates`.
+    pub async fn save_precomputed_ticker_daily_values(
+        &self,
+        account: Option<AccountType>,
+        dates: &[NaiveDate],
+        values: &HashMap<String, Vec<Decimal>>,
+    ) -> Result<()> {
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_ticker_daily_values");
-        let filter = doc! { "date": date.to_string(), "ticker": ticker };
-        let update = doc! {
-            "$set": {
+        coll.delete_many(doc! { "account": view_key(account) }).await?;
+        let last_updated = Utc::now().to_rfc3339();
+        let docs: Vec<Document> = values.iter()
+            .flat_map(|(ticker, series)| dates.iter().zip(series).map(move |(date, value)| (ticker, date, value)))
+            .map(|(ticker, date, value)| doc! {
+                "account": view_key(account),
                 "date": date.to_string(),
                 "ticker": ticker,
                 "daily_value": value.to_string(),
-                "last_updated": Utc::now().to_rfc3339(),
-            }
-        };
-        coll.update_one(filter, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
+                "last_updated": &last_updated,
+            })
+            .collect();
+        if !docs.is_empty() {
+            coll.insert_many(docs).await?;
+        }
         Ok(())
     }
 
-    pub async fn save_precomputed_monthly_contribution(&self, month: &str, value: Decimal) -> Result<()> {
+    /// Replaces one view's net contributions, keyed by `YYYY-MM`.
+    pub async fn save_precomputed_monthly_contributions(&self, account: Option<AccountType>, months: &HashMap<String, Decimal>) -> Result<()> {
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_monthly_contributions");
-        let filter = doc! { "month": month };
-        let update = doc! {
-            "$set": {
+        coll.delete_many(doc! { "account": view_key(account) }).await?;
+        if months.is_empty() {
+            return Ok(());
+        }
+        let last_updated = Utc::now().to_rfc3339();
+        let docs: Vec<Document> = months.iter()
+            .map(|(month, value)| doc! {
+                "account": view_key(account),
                 "month": month,
                 "net_value": value.to_string(),
+                "last_updated": &last_updated,
+            })
+            .collect();
+        coll.insert_many(docs).await?;
+        Ok(())
+    }
+
+    pub async fn save_precomputed_metrics(&self, account: Option<AccountType>, stats: &PortfolioStats) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_metrics");
+        let filter = doc! { "account": view_key(account) };
+        let update = doc! {
+            "$set": {
+                "account": view_key(account),
+                "irr": Decimal::from_f64(stats.irr).unwrap_or_default().to_string(),
+                "twr": Decimal::from_f64(stats.twr).unwrap_or_default().to_string(),
+                "total_invested": stats.total_invested.to_string(),
+                "current_value": stats.current_value.to_string(),
+                "profit_loss": stats.profit_loss.to_string(),
+                "return_percentage": stats.return_percentage.to_string(),
+                "calc_date": stats.calc_date.to_string(),
+                "currency": &stats.currency,
                 "last_updated": Utc::now().to_rfc3339(),
             }
         };
@@ -421,59 +730,143 @@ impl Database {
         Ok(())
     }
 
-    pub async fn save_precomputed_metrics(&self, irr: Decimal, twr: Decimal, invested: Decimal, current: Decimal, pl: Decimal, ret_pct: Decimal, calc_date: &str) -> Result<()> {
+    /// Stored next to the return metrics of the same view.
+    pub async fn save_precomputed_risk_metrics(&self, account: Option<AccountType>, risk: &RiskMetrics) -> Result<()> {
         let coll = self.db.collection::<mongodb::bson::Document>("precomputed_portfolio_metrics");
-        let filter = doc! { "id": 1 };
+        let filter = doc! { "account": view_key(account) };
         let update = doc! {
             "$set": {
-                "id": 1,
-                "irr": irr.to_string(),
-                "twr": twr.to_string(),
-                "total_invested": invested.to_string(),
-                "current_value": current.to_string(),
-                "profit_loss": pl.to_string(),
-                "return_percentage": ret_pct.to_string(),
-                "calc_date": calc_date,
-                "last_updated": Utc::now().to_rfc3339(),
+                "account": view_key(account),
+                "risk": mongodb::bson::to_bson(risk)?,
             }
         };
         coll.update_one(filter, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
         Ok(())
     }
 
-    pub async fn save_trades(&self, records: &[TradingRecord]) -> Result<()> {
-        let coll = self.db.collection::<mongodb::bson::Document>("trades");
-        coll.delete_many(doc! {}).await?;
-        
-        if records.is_empty() {
+    /// Replaces one view's per-holding metrics.
+    pub async fn save_precomputed_holding_metrics(&self, account: Option<AccountType>, holdings: &[HoldingStats]) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_holding_metrics");
+        coll.delete_many(doc! { "account": view_key(account) }).await?;
+        if holdings.is_empty() {
+            return Ok(());
+        }
+        let last_updated = Utc::now().to_rfc3339();
+        let docs: Vec<Document> = holdings.iter()
+            .map(|h| doc! {
+                "account": view_key(account),
+                "ticker": &h.ticker,
+                "irr": Decimal::from_f64(h.irr).unwrap_or_default().to_string(),
+                "twr": Decimal::from_f64(h.twr).unwrap_or_default().to_string(),
+                "invested": h.invested.to_string(),
+                "proceeds": h.proceeds.to_string(),
+                "income": h.income.to_string(),
+                "current_value": h.current_value.to_string(),
+                "gain": h.gain.to_string(),
+                "return_percentage": h.return_percentage.to_string(),
+                "currency": &h.currency,
+                "last_updated": &last_updated,
+            })
+            .collect();
+        coll.insert_many(docs).await?;
+        Ok(())
+    }
+
+    pub async fn get_holding_metrics(&self, account: Option<AccountType>) -> Result<Vec<serde_json::Value>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_holding_metrics");
+        let find_options = FindOptions::builder().sort(doc! { "ticker": 1 }).build();
+        let mut cursor = coll.find(doc! { "account": view_key(account) }).with_options(find_options).await?;
+
+        let number = |doc: &Document, key: &str| doc.get_str(key).ok().and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+        let mut holdings = Vec::new();
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            holdings.push(serde_json::json!({
+                "ticker": doc.get_str("ticker")?,
+                "irr": number(&doc, "irr"),
+                "twr": number(&doc, "twr"),
+                "invested": number(&doc, "invested"),
+                "proceeds": number(&doc, "proceeds"),
+                "income": number(&doc, "income"),
+                "current_value": number(&doc, "current_value"),
+                "gain": number(&doc, "gain"),
+                "return_percentage": number(&doc, "return_percentage"),
+                "currency": doc.get_str("currency").unwrap_or("GBP"),
+                "last_updated": doc.get_str("last_updated").unwrap_or(""),
+            }));
+        }
+        Ok(holdings)
+    }
+
+    /// Replaces one view's period returns, keeping their order.
+    pub async fn save_precomputed_period_returns(&self, account: Option<AccountType>, periods: &[PeriodReturn]) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_period_returns");
+        coll.delete_many(doc! { "account": view_key(account) }).await?;
+        if periods.is_empty() {
             return Ok(());
         }
+        let mut docs = Vec::new();
+        for (position, period) in periods.iter().enumerate() {
+            let mut doc = mongodb::bson::to_document(period)?;
+            doc.insert("account", view_key(account));
+            doc.insert("position", position as i64);
+            docs.push(doc);
+        }
+        coll.insert_many(docs).await?;
+        Ok(())
+    }
+
+    pub async fn get_period_returns(&self, account: Option<AccountType>) -> Result<Vec<PeriodReturn>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("precomputed_period_returns");
+        let find_options = FindOptions::builder().sort(doc! { "position": 1 }).build();
+        let mut cursor = coll.find(doc! { "account": view_key(account) }).with_options(find_options).await?;
 
-        let docs: Vec<mongodb::bson::Document> = records.iter().map(|r| {
+        let mut periods = Vec::new();
+        while let Some(result) = cursor.next().await {
+            periods.push(mongodb::bson::from_document(result?)?);
+        }
+        Ok(periods)
+    }
+
+    /// Appends trades, skipping rows already stored (see `dedup::NaturalKey`).
+    pub async fn save_trades(&self, records: &[TradingRecord]) -> Result<MergeSummary> {
+        let existing = self.load_trades().await?;
+        let (new_records, summary) = partition_new_records(&existing, records);
+
+        if new_records.is_empty() {
+            return Ok(summary);
+        }
+
+        let coll = self.db.collection::<mongodb::bson::Document>("trades");
+        let docs: Vec<mongodb::bson::Document> = new_records.iter().map(|r| {
             doc! {
                 "security_isin": &r.security_isin,
-                "transaction_type": &r.transaction_type,
+                "transaction_type": r.transaction_type.as_str(),
                 "quantity": r.quantity.to_string(),
                 "share_price": r.share_price.to_string(),
                 "total_trade_value": r.total_trade_value.to_string(),
                 "trade_date_time": r.trade_date_time.format("%Y-%m-%d %H:%M:%S").to_string(),
                 "settlement_date": r.settlement_date.format("%Y-%m-%d %H:%M:%S").to_string(),
                 "broker": &r.broker,
-                "account_type": &r.account_type,
+                "account_type": r.account_type.as_str(),
                 "ticker": &r.ticker,
             }
         }).collect();
 
         coll.insert_many(docs).await?;
-        Ok(())
+        Ok(summary)
     }
 
     pub async fn load_trades(&self) -> Result<Vec<TradingRecord>> {
+        self.load_trades_for_account(None).await
+    }
+
+    pub async fn load_trades_for_account(&self, account: Option<AccountType>) -> Result<Vec<TradingRecord>> {
         // Load mappings first for manual JOIN equivalent
         let mappings = self.get_isin_ticker_map().await?;
         
         let coll = self.db.collection::<mongodb::bson::Document>("trades");
-        let mut cursor = coll.find(doc! {}).await?;
+        let mut cursor = coll.find(account_filter(account)).await?;
         
         let mut results = Vec::new();
         while let Some(result) = cursor.next().await {
@@ -498,20 +891,25 @@ impl Database {
                 .unwrap_or_default();
 
             let isin = doc.get_str("security_isin")?;
+            let raw_type = doc.get_str("transaction_type")?;
+            let transaction_type = TransactionKind::parse(raw_type);
+            if transaction_type == TransactionKind::Other {
+                warn!("Stored trade for {} has unknown transaction type '{}'; it is ignored", isin, raw_type);
+            }
             // Prefer mapped_ticker from isin_to_ticker collection
             let ticker = mappings.get(isin).cloned()
                 .or_else(|| doc.get_str("ticker").ok().map(|s| s.to_string()));
 
             results.push(TradingRecord {
                 security_isin: isin.to_string(),
-                transaction_type: doc.get_str("transaction_type")?.to_string(),
+                transaction_type,
                 quantity: Decimal::from_str(doc.get_str("quantity")?).unwrap_or_default(),
                 share_price: Decimal::from_str(doc.get_str("share_price")?).unwrap_or_default(),
                 total_trade_value: Decimal::from_str(doc.get_str("total_trade_value")?).unwrap_or_default(),
                 trade_date_time: trade_dt,
                 settlement_date: sett_dt,
                 broker: doc.get_str("broker")?.to_string(),
-                account_type: doc.get_str("account_type")?.to_string(),
+                account_type: AccountType::parse(doc.get_str("account_type")?),
                 ticker,
             });
         }
@@ -531,51 +929,67 @@ impl Database {
         Ok(map)
     }
 
-    pub async fn save_cash_flows(&self, records: &[CashRecord]) -> Result<()> {
-        let coll = self.db.collection::<mongodb::bson::Document>("cash_flows");
-        coll.delete_many(doc! {}).await?;
-        
-        if records.is_empty() {
-            return Ok(());
+    /// Appends cash rows, skipping rows already stored (see `dedup::NaturalKey`).
+    pub async fn save_cash_flows(&self, records: &[CashRecord]) -> Result<MergeSummary> {
+        let existing = self.load_cash_flows().await?;
+        let (new_records, summary) = partition_new_records(&existing, records);
+
+        if new_records.is_empty() {
+            return Ok(summary);
         }
 
-        let docs: Vec<mongodb::bson::Document> = records.iter().map(|r| {
+        let coll = self.db.collection::<mongodb::bson::Document>("cash_flows");
+        let docs: Vec<mongodb::bson::Document> = new_records.iter().map(|r| {
             doc! {
                 "date": r.date.to_string(),
                 "activity": &r.activity,
                 "credit": r.credit.map(|c| c.to_string()),
                 "debit": r.debit.map(|d| d.to_string()),
-                "balance": r.balance.to_string(),
-                "account_type": &r.account_type,
+                "balance": r.balance.map(|b| b.to_string()),
+                "account_type": r.account_type.as_str(),
                 "net_flow": r.net_flow.to_string(),
+                "kind": r.kind.as_str(),
             }
         }).collect();
 
         coll.insert_many(docs).await?;
-        Ok(())
+        Ok(summary)
     }
 
     pub async fn load_cash_flows(&self) -> Result<Vec<CashRecord>> {
+        self.load_cash_flows_for_account(None).await
+    }
+
+    pub async fn load_cash_flows_for_account(&self, account: Option<AccountType>) -> Result<Vec<CashRecord>> {
         let coll = self.db.collection::<mongodb::bson::Document>("cash_flows");
-        let mut cursor = coll.find(doc! {}).await?;
+        let mut cursor = coll.find(account_filter(account)).await?;
         
         let mut results = Vec::new();
         while let Some(result) = cursor.next().await {
             let doc = result?;
+            let activity = doc.get_str("activity")?;
+            let net_flow = Decimal::from_str(doc.get_str("net_flow")?).unwrap_or_default();
+            // Rows stored before classification existed have no kind yet
+            let kind = doc.get_str("kind").ok()
+                .and_then(CashActivityKind::parse)
+                .unwrap_or_else(|| CashActivityKind::classify(activity, net_flow));
             results.push(CashRecord {
                 date: NaiveDate::parse_from_str(doc.get_str("date")?, "%Y-%m-%d").unwrap_or_default(),
-                activity: doc.get_str("activity")?.to_string(),
+                activity: activity.to_string(),
                 credit: doc.get_str("credit").ok().and_then(|s| Decimal::from_str(s).ok()),
                 debit: doc.get_str("debit").ok().and_then(|s| Decimal::from_str(s).ok()),
-                balance: Decimal::from_str(doc.get_str("balance")?).unwrap_or_default(),
-                account_type: doc.get_str("account_type")?.to_string(),
-                net_flow: Decimal::from_str(doc.get_str("net_flow")?).unwrap_or_default(),
+                balance: doc.get_str("balance").ok().and_then(|s| Decimal::from_str(s).ok()),
+                account_type: AccountType::parse(doc.get_str("account_type")?),
+                net_flow,
+                kind,
             });
         }
         Ok(results)
     }
 
-    pub async fn save_isin_ticker_mapping(&self, isin: &str, ticker: &str, security_name: Option<&str>) -> Result<()> {
+    /// Creates or updates a mapping. Without a `quote_unit` any stored unit is cleared so it
+    /// is detected again for the (possibly new) ticker.
+    pub async fn save_isin_ticker_mapping(&self, isin: &str, ticker: &str, security_name: Option<&str>, quote_unit: Option<&str>) -> Result<()> {
         let coll = self.db.collection::<mongodb::bson::Document>("isin_to_ticker");
         let filter = doc! { "isin": isin };
         let mut set_doc = doc! {
@@ -587,10 +1001,14 @@ impl Database {
             set_doc.insert("security_name", name);
         }
         
-        let update = doc! {
+        let mut update = doc! {
             "$set": set_doc,
             "$setOnInsert": { "created_at": Utc::now().to_rfc3339() }
         };
+        match quote_unit {
+            Some(unit) => update.get_document_mut("$set")?.insert("quote_unit", unit),
+            None => update.insert("$unset", doc! { "quote_unit": "" }),
+        };
         coll.update_one(filter, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
         Ok(())
     }
@@ -601,6 +1019,12 @@ impl Database {
         Ok(doc_opt.and_then(|d| d.get_str("ticker").ok().map(|s| s.to_string())))
     }
 
+    pub async fn get_security_name_for_isin(&self, isin: &str) -> Result<Option<String>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("isin_to_ticker");
+        let doc_opt = coll.find_one(doc! { "isin": isin }).await?;
+        Ok(doc_opt.and_then(|d| d.get_str("security_name").ok().map(|s| s.to_string())))
+    }
+
     pub async fn get_all_isin_ticker_mappings(&self) -> Result<Vec<serde_json::Value>> {
         let coll = self.db.collection::<mongodb::bson::Document>("isin_to_ticker");
         let find_options = FindOptions::builder().sort(doc! { "isin": 1 }).build();
@@ -613,6 +1037,7 @@ impl Database {
                 "isin": doc.get_str("isin")?,
                 "ticker": doc.get_str("ticker")?,
                 "security_name": doc.get_str("security_name").ok(),
+                "quote_unit": doc.get_str("quote_unit").ok(),
                 "created_at": doc.get_str("created_at").unwrap_or(""),
                 "updated_at": doc.get_str("updated_at").unwrap_or(""),
             }));
@@ -620,6 +1045,41 @@ impl Database {
         Ok(results)
     }
 
+    /// Quote units by ISIN for the mappings that have one.
+    pub async fn get_quote_units(&self) -> Result<std::collections::HashMap<String, String>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("isin_to_ticker");
+        let mut cursor = coll.find(doc! { "quote_unit": { "$exists": true } }).await?;
+        let mut units = std::collections::HashMap::new();
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            units.insert(doc.get_str("isin")?.to_string(), doc.get_str("quote_unit")?.to_string());
+        }
+        Ok(units)
+    }
+
+    /// The currency prices for `key` (an ISIN or ticker) are quoted in: the mapping's quote
+    /// unit, else that of the latest cached provider bar. None when neither is known.
+    pub async fn get_quote_currency(&self, key: &str) -> Result<Option<String>> {
+        let mappings = self.db.collection::<mongodb::bson::Document>("isin_to_ticker");
+        let mapping = mappings.find_one(doc! { "$or": [{ "isin": key }, { "ticker": key }] }).await?;
+        if let Some(unit) = mapping.as_ref().and_then(|m| m.get_str("quote_unit").ok()) {
+            return Ok(Some(unit.to_string()));
+        }
+        let ticker = mapping.as_ref().and_then(|m| m.get_str("ticker").ok()).unwrap_or(key);
+        let prices = self.db.collection::<mongodb::bson::Document>("prices");
+        let latest = prices.find_one(doc! { "ticker": ticker })
+            .with_options(FindOneOptions::builder().sort(doc! { "date": -1 }).build())
+            .await?;
+        Ok(latest.and_then(|d| d.get_str("currency").ok().map(normalise_currency)))
+    }
+
+    /// Stores a detected quote unit without touching the rest of the mapping.
+    pub async fn set_quote_unit(&self, isin: &str, quote_unit: &str) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("isin_to_ticker");
+        coll.update_one(doc! { "isin": isin }, doc! { "$set": { "quote_unit": quote_unit } }).await?;
+        Ok(())
+    }
+
     pub async fn get_isins_without_mappings(&self) -> Result<Vec<String>> {
         // This is a bit more complex in Mongo if we want to do it in one query, 
         // but we can just get all unique ISINs from trades and subtract mapped ones.
@@ -646,35 +1106,171 @@ impl Database {
         Ok(res.deleted_count > 0)
     }
 
-    pub async fn save_price(&self, ticker: &str, date: NaiveDate, close: Decimal) -> Result<()> {
+    /// Replaces the cached bars for `ticker` within `from..=to` with `bars`.
+    pub async fn save_prices(&self, ticker: &str, from: NaiveDate, to: NaiveDate, bars: &[(NaiveDate, Decimal, String)]) -> Result<()> {
         let coll = self.db.collection::<mongodb::bson::Document>("prices");
-        let filter = doc! { "ticker": ticker, "date": date.to_string() };
-        let update = doc! {
-            "$set": {
+        coll.delete_many(doc! {
+            "ticker": ticker,
+            "date": { "$gte": from.to_string(), "$lte": to.to_string() },
+        }).await?;
+
+        if bars.is_empty() {
+            return Ok(());
+        }
+        let docs: Vec<mongodb::bson::Document> = bars.iter().map(|(date, close, currency)| {
+            doc! {
                 "ticker": ticker,
                 "date": date.to_string(),
                 "close": close.to_string(),
+                "currency": currency,
             }
-        };
-        coll.update_one(filter, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
+        }).collect();
+        coll.insert_many(docs).await?;
         Ok(())
     }
 
-    pub async fn get_price(&self, ticker: &str, date: NaiveDate) -> Result<Option<Decimal>> {
+    pub async fn load_prices(&self, ticker: &str, from: NaiveDate, to: NaiveDate) -> Result<Vec<(NaiveDate, Decimal, String)>> {
         let coll = self.db.collection::<mongodb::bson::Document>("prices");
-        let doc_opt = coll.find_one(doc! { "ticker": ticker, "date": date.to_string() }).await?;
-        if let Some(doc) = doc_opt {
-            Ok(Some(Decimal::from_str(doc.get_str("close")?).unwrap_or_default()))
-        } else {
-            Ok(None)
+        let filter = doc! {
+            "ticker": ticker,
+            "date": { "$gte": from.to_string(), "$lte": to.to_string() },
+        };
+        let find_options = FindOptions::builder().sort(doc! { "date": 1 }).build();
+        let mut cursor = coll.find(filter).with_options(find_options).await?;
+
+        let mut results = Vec::new();
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            results.push((
+                NaiveDate::parse_from_str(doc.get_str("date")?, "%Y-%m-%d").unwrap_or_default(),
+                Decimal::from_str(doc.get_str("close")?).unwrap_or_default(),
+                doc.get_str("currency").unwrap_or("GBP").to_string(),
+            ));
+        }
+        Ok(results)
+    }
+
+    pub async fn get_price_coverage(&self, ticker: &str) -> Result<Option<PriceCoverage>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("price_coverage");
+        let Some(doc) = coll.find_one(doc! { "ticker": ticker }).await? else {
+            return Ok(None);
+        };
+        // Bars cached by an older version may have been rescaled; treat them as missing
+        if doc.get_i32("version").unwrap_or(0) != PRICE_CACHE_VERSION {
+            return Ok(None);
+        }
+        let date = |key: &str| -> Result<NaiveDate> {
+            Ok(NaiveDate::parse_from_str(doc.get_str(key)?, "%Y-%m-%d")?)
+        };
+        Ok(Some(PriceCoverage { from: date("from")?, to: date("to")?, fetched_on: date("fetched_on")? }))
+    }
+
+    pub async fn save_price_coverage(&self, ticker: &str, coverage: &PriceCoverage) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("price_coverage");
+        let update = doc! {
+            "$set": {
+                "ticker": ticker,
+                "from": coverage.from.to_string(),
+                "to": coverage.to.to_string(),
+                "fetched_on": coverage.fetched_on.to_string(),
+                "version": PRICE_CACHE_VERSION,
+            }
+        };
+        coll.update_one(doc! { "ticker": ticker }, update).with_options(UpdateOptions::builder().upsert(true).build()).await?;
+        Ok(())
+    }
+
+    /// Upserts manual prices; an entry for an existing (key, date) replaces it.
+    pub async fn save_manual_prices(&self, prices: &[ManualPrice]) -> Result<usize> {
+        let coll = self.db.collection::<mongodb::bson::Document>("manual_prices");
+        for p in prices {
+            let update = doc! {
+                "$set": {
+                    "key": &p.key,
+                    "date": p.date.to_string(),
+                    "price": p.price.to_string(),
+                    "currency": &p.currency,
+                    "updated_at": Utc::now().to_rfc3339(),
+                }
+            };
+            coll.update_one(doc! { "key": &p.key, "date": p.date.to_string() }, update)
+                .with_options(UpdateOptions::builder().upsert(true).build()).await?;
+        }
+        Ok(prices.len())
+    }
+
+    /// Manual prices for one key, or all of them, ordered by key then date.
+    pub async fn load_manual_prices(&self, key: Option<&str>) -> Result<Vec<ManualPrice>> {
+        let coll = self.db.collection::<mongodb::bson::Document>("manual_prices");
+        let filter = match key {
+            Some(k) => doc! { "key": k },
+            None => doc! {},
+        };
+        let find_options = FindOptions::builder().sort(doc! { "key": 1, "date": 1 }).build();
+        let mut cursor = coll.find(filter).with_options(find_options).await?;
+
+        let mut results = Vec::new();
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            results.push(ManualPrice {
+                key: doc.get_str("key")?.to_string(),
+                date: NaiveDate::parse_from_str(doc.get_str("date")?, "%Y-%m-%d")?,
+                price: Decimal::from_str(doc.get_str("price")?)?,
+                currency: doc.get_str("currency").unwrap_or("GBP").to_string(),
+            });
+        }
+        Ok(results)
+    }
+
+    pub async fn delete_manual_price(&self, key: &str, date: NaiveDate) -> Result<bool> {
+        let coll = self.db.collection::<mongodb::bson::Document>("manual_prices");
+        let res = coll.delete_one(doc! { "key": key, "date": date.to_string() }).await?;
+        Ok(res.deleted_count > 0)
+    }
+
+    /// Replaces the stored results of the last mapping verification.
+    pub async fn save_mapping_verifications(&self, results: &[MappingVerification]) -> Result<()> {
+        let coll = self.db.collection::<mongodb::bson::Document>("mapping_verifications");
+        coll.delete_many(doc! {}).await?;
+        if results.is_empty() {
+            return Ok(());
+        }
+        let checked_at = Utc::now().to_rfc3339();
+        let mut docs = Vec::new();
+        for r in results {
+            let mut doc = mongodb::bson::to_document(r)?;
+            doc.insert("checked_at", &checked_at);
+            docs.push(doc);
+        }
+        coll.insert_many(docs).await?;
+        Ok(())
+    }
+
+    /// Results of the last mapping verification with the time it ran, ordered by ISIN.
+    pub async fn load_mapping_verifications(&self) -> Result<(Vec<MappingVerification>, Option<String>)> {
+        let coll = self.db.collection::<mongodb::bson::Document>("mapping_verifications");
+        let find_options = FindOptions::builder().sort(doc! { "isin": 1 }).build();
+        let mut cursor = coll.find(doc! {}).with_options(find_options).await?;
+
+        let mut results = Vec::new();
+        let mut checked_at = None;
+        while let Some(result) = cursor.next().await {
+            let doc = result?;
+            if checked_at.is_none() {
+                checked_at = doc.get_str("checked_at").ok().map(str::to_string);
+            }
+            results.push(mongodb::bson::from_document(doc)?);
         }
+        Ok((results, checked_at))
     }
 
     pub async fn reset(&self) -> Result<()> {
         self.db.collection::<Bson>("trades").delete_many(doc! {}).await?;
         self.db.collection::<Bson>("cash_flows").delete_many(doc! {}).await?;
         self.db.collection::<Bson>("prices").delete_many(doc! {}).await?;
-        // We keep isin_to_ticker mapping as it is valuable to keep
+        self.db.collection::<Bson>("price_coverage").delete_many(doc! {}).await?;
+        self.db.collection::<Bson>("mapping_verifications").delete_many(doc! {}).await?;
+        // We keep isin_to_ticker mapping and manual_prices as they are entered by hand
         self.clear_precomputed_data().await?;
         Ok(())
     }
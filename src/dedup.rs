@@ -0,0 +1,162 @@
+use crate::models::{CashRecord, TradingRecord};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Stable identity of a statement row, independent of which export it came from.
+///
+/// `natural_key` identifies the row; `fingerprint` covers the remaining fields so that
+/// a row with a known key but different details is reported as a conflict instead of
+/// being silently skipped.
+pub trait NaturalKey {
+    fn natural_key(&self) -> String;
+    fn fingerprint(&self) -> String;
+}
+
+impl NaturalKey for TradingRecord {
+    fn natural_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.account_type,
+            self.security_isin,
+            self.trade_date_time.format("%Y-%m-%d %H:%M:%S"),
+            self.quantity.normalize(),
+            self.total_trade_value.normalize(),
+        )
+    }
+
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}",
+            self.transaction_type,
+            self.share_price.normalize(),
+            self.settlement_date.format("%Y-%m-%d %H:%M:%S"),
+            self.broker,
+        )
+    }
+}
+
+impl NaturalKey for CashRecord {
+    fn natural_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.account_type,
+            self.date,
+            self.activity,
+            self.credit.map(|c| c.normalize().to_string()).unwrap_or_default(),
+            self.debit.map(|d| d.normalize().to_string()).unwrap_or_default(),
+        )
+    }
+
+    fn fingerprint(&self) -> String {
+        self.balance.map(|b| b.normalize().to_string()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MergeSummary {
+    pub inserted: usize,
+    pub duplicates: usize,
+    pub conflicts: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicting_keys: Vec<String>,
+}
+
+/// Keys every record, suffixing an occurrence counter so that genuinely repeated rows
+/// within one statement (e.g. two identical deposits on the same day) stay distinct.
+fn keyed<T: NaturalKey>(records: &[T]) -> Vec<(String, String)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    records.iter().map(|r| {
+        let key = r.natural_key();
+        let n = seen.entry(key.clone()).or_insert(0);
+        let occurrence_key = format!("{}#{}", key, n);
+        *n += 1;
+        (occurrence_key, r.fingerprint())
+    }).collect()
+}
+
+/// Splits `incoming` into rows not yet present in `existing`, reporting duplicates and conflicts.
+pub fn partition_new_records<T: NaturalKey + Clone>(existing: &[T], incoming: &[T]) -> (Vec<T>, MergeSummary) {
+    let existing_keys: HashMap<String, String> = keyed(existing).into_iter().collect();
+    let mut summary = MergeSummary::default();
+    let mut new_records = Vec::new();
+
+    for ((key, fingerprint), record) in keyed(incoming).into_iter().zip(incoming) {
+        match existing_keys.get(&key) {
+            None => {
+                summary.inserted += 1;
+                new_records.push(record.clone());
+            }
+            // An empty fingerprint has nothing to compare, e.g. a cash row without a balance
+            Some(existing_fingerprint)
+                if *existing_fingerprint == fingerprint || existing_fingerprint.is_empty() || fingerprint.is_empty() =>
+            {
+                summary.duplicates += 1;
+            }
+            Some(_) => {
+                summary.conflicts += 1;
+                summary.conflicting_keys.push(key);
+            }
+        }
+    }
+
+    (new_records, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AccountType, CashActivityKind};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn cash(day: u32, credit: rust_decimal::Decimal, balance: rust_decimal::Decimal) -> CashRecord {
+        CashRecord {
+            date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            activity: "Payment Received".to_string(),
+            credit: Some(credit),
+            debit: None,
+            balance: Some(balance),
+            account_type: AccountType::ISA,
+            net_flow: credit,
+            kind: CashActivityKind::Deposit,
+        }
+    }
+
+    #[test]
+    fn test_overlapping_upload_skips_duplicates() {
+        let existing = vec![cash(1, dec!(100), dec!(100)), cash(2, dec!(50), dec!(150))];
+        let incoming = vec![cash(2, dec!(50.00), dec!(150)), cash(3, dec!(25), dec!(175))];
+
+        let (new_records, summary) = partition_new_records(&existing, &incoming);
+        assert_eq!(summary.inserted, 1);
+        assert_eq!(summary.duplicates, 1);
+        assert_eq!(summary.conflicts, 0);
+        assert_eq!(new_records[0].date, NaiveDate::from_ymd_opt(2024, 1, 3).unwrap());
+    }
+
+    #[test]
+    fn test_repeated_rows_and_conflicts() {
+        let existing = vec![cash(1, dec!(100), dec!(100))];
+        let incoming = vec![cash(1, dec!(100), dec!(100)), cash(1, dec!(100), dec!(200)), cash(4, dec!(10), dec!(999))];
+        let (_, summary) = partition_new_records(&existing, &incoming);
+        // The second identical deposit on the same day is a new row, not a duplicate
+        assert_eq!(summary.inserted, 2);
+        assert_eq!(summary.duplicates, 1);
+
+        let existing = vec![cash(1, dec!(100), dec!(100))];
+        let incoming = vec![cash(1, dec!(100), dec!(120))];
+        let (new_records, summary) = partition_new_records(&existing, &incoming);
+        assert!(new_records.is_empty());
+        assert_eq!(summary.conflicts, 1);
+    }
+
+    #[test]
+    fn test_rows_without_balance_are_duplicates() {
+        let without_balance = |day, credit| CashRecord { balance: None, ..cash(day, credit, credit) };
+        let existing = vec![without_balance(1, dec!(100)), without_balance(2, dec!(50))];
+        let incoming = vec![without_balance(2, dec!(50)), without_balance(3, dec!(25))];
+        let (new_records, summary) = partition_new_records(&existing, &incoming);
+        assert_eq!((summary.inserted, summary.duplicates, summary.conflicts), (1, 1, 0));
+        assert_eq!(new_records[0].date, NaiveDate::from_ymd_opt(2024, 1, 3).unwrap());
+    }
+}
@@ -0,0 +1,6 @@
+use std::str::FromStr;
+
+/// Parses environment variable `name`; none when it is unset or does not parse.
+pub fn parse_var<T: FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
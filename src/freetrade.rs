@@ -0,0 +1,79 @@
+use crate::merge_csv::StatementInfo;
+use crate::models::TransactionKind;
+use crate::statement_parser::{
+    detect_by_header, for_each_row, normalised_cash, normalised_trade, ParsedStatement,
+    StatementParser,
+};
+
+const BROKER: &str = "Freetrade";
+const REQUIRED_COLUMNS: &[&str] = &["Title", "Type", "Timestamp", "Total Amount", "Buy / Sell", "ISIN"];
+const TIME_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.fZ", "%Y-%m-%dT%H:%M:%SZ", "%Y-%m-%d %H:%M:%S"];
+
+/// Freetrade activity export: orders, top-ups, withdrawals, dividends and interest in one table.
+pub struct FreetradeParser;
+
+impl StatementParser for FreetradeParser {
+    fn broker(&self) -> &'static str {
+        BROKER
+    }
+
+    fn detect(&self, filename: &str, content: &str) -> Option<StatementInfo> {
+        detect_by_header(filename, content, REQUIRED_COLUMNS)
+    }
+
+    fn parse(&self, filename: &str, content: &str, info: &StatementInfo) -> ParsedStatement {
+        let account = info.account_type;
+        let mut trades = Vec::new();
+        let mut cash = Vec::new();
+
+        let issues = for_each_row(filename, content, |row| {
+            let row_type = row.get("Type");
+            if row_type == "MONTHLY_STATEMENT" {
+                return Ok(());
+            }
+            let time = row.datetime("Timestamp", TIME_FORMATS)?;
+
+            match row_type {
+                "ORDER" => {
+                    let is_buy = match row.get("Buy / Sell") {
+                        "BUY" => true,
+                        "SELL" => false,
+                        _ => return Err(row.issue("Buy / Sell", "Expected BUY or SELL")),
+                    };
+                    let total = row.decimal("Total Amount")?.abs();
+                    trades.push(normalised_trade(
+                        row.get("Title"),
+                        row.get("ISIN"),
+                        if is_buy { TransactionKind::Buy } else { TransactionKind::Sell },
+                        row.decimal("Quantity")?,
+                        row.decimal("Price per Share in Account Currency")?,
+                        total,
+                        time,
+                        BROKER,
+                        account,
+                    ));
+                    let activity = format!("{}: {}", if is_buy { "Buy" } else { "Sell" }, row.get("Ticker"));
+                    cash.push(normalised_cash(time, &activity, if is_buy { -total } else { total }, account));
+                }
+                "TOP_UP" => {
+                    cash.push(normalised_cash(time, "Payment Received", row.decimal("Total Amount")?.abs(), account));
+                }
+                "WITHDRAWAL" => {
+                    cash.push(normalised_cash(time, "Withdrawal", -row.decimal("Total Amount")?.abs(), account));
+                }
+                "DIVIDEND" => {
+                    let activity = format!("Dividend: {}", row.get("Ticker"));
+                    cash.push(normalised_cash(time, &activity, row.decimal("Total Amount")?, account));
+                }
+                "INTEREST_FROM_CASH" => {
+                    cash.push(normalised_cash(time, "Interest", row.decimal("Total Amount")?, account));
+                }
+                _ => return Err(row.issue("Type", "Unsupported Freetrade activity type")),
+            }
+            Ok(())
+        });
+
+        cash.sort_by_key(|c| c.date);
+        ParsedStatement { trades, cash, issues }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::models::TradingRecord;
+use rust_decimal::Decimal;
+
+/// Units held in one security.
+///
+/// The quantity goes negative when the statements dispose of more than they acquired, as
+/// the reconciliation's oversold check reports.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub quantity: Decimal,
+}
+
+impl Position {
+    pub fn apply(&mut self, trade: &TradingRecord) {
+        self.quantity += trade.transaction_type.holding_change(trade.quantity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AccountType, TransactionKind};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn trade(kind: TransactionKind, quantity: Decimal, value: Decimal) -> TradingRecord {
+        let dt = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        TradingRecord {
+            security_isin: "IE00BK5BQT80".to_string(),
+            transaction_type: kind,
+            quantity,
+            share_price: value / quantity,
+            total_trade_value: value,
+            trade_date_time: dt,
+            settlement_date: dt,
+            broker: "Winterflood".to_string(),
+            account_type: AccountType::ISA,
+            ticker: Some("VWRP.L".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_position_through_reinvestment_and_sale() {
+        let mut position = Position::default();
+        position.apply(&trade(TransactionKind::Buy, dec!(10), dec!(1000)));
+        position.apply(&trade(TransactionKind::DividendReinvestment, dec!(1), dec!(120)));
+        assert_eq!(position.quantity, dec!(11));
+
+        position.apply(&trade(TransactionKind::Sell, dec!(5.5), dec!(700)));
+        assert_eq!(position.quantity, dec!(5.5));
+
+        // Unknown types are ignored
+        position.apply(&trade(TransactionKind::Other, dec!(3), dec!(300)));
+        assert_eq!(position.quantity, dec!(5.5));
+
+        position.apply(&trade(TransactionKind::TransferOut, dec!(5.5), dec!(600)));
+        assert_eq!(position, Position::default());
+
+        // Reinvested dividends add units without counting as new money
+        assert_eq!(TransactionKind::DividendReinvestment.contribution(dec!(120)), Decimal::ZERO);
+        assert_eq!(TransactionKind::Sell.contribution(dec!(700)), dec!(-700));
+    }
+
+    #[test]
+    fn test_oversold_position_stays_negative() {
+        let mut position = Position::default();
+        position.apply(&trade(TransactionKind::Buy, dec!(2), dec!(200)));
+        position.apply(&trade(TransactionKind::Sell, dec!(5), dec!(550)));
+        assert_eq!(position.quantity, dec!(-3));
+
+        position.apply(&trade(TransactionKind::Buy, dec!(4), dec!(440)));
+        assert_eq!(position.quantity, dec!(1));
+    }
+}
@@ -7,3 +7,24 @@ pub mod prices;
 pub mod portfolio_stats;
 pub mod background_processor;
 pub mod rebalance;
+pub mod dedup;
+pub mod preview;
+pub mod statement_parser;
+pub mod trading212;
+pub mod freetrade;
+pub mod cash_activity;
+pub mod holdings;
+pub mod price_cache;
+pub mod env;
+pub mod price_source;
+pub mod csv_prices;
+pub mod manual_prices;
+pub mod quote_unit;
+pub mod price_fetch;
+pub mod mapping_check;
+pub mod trade_prices;
+pub mod data_quality;
+pub mod cash_balance;
+pub mod reconciliation;
+pub mod period_returns;
+pub mod risk;
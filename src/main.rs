@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Multipart, State, Path},
+    extract::{Multipart, State, Path, Query},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post, delete},
@@ -7,13 +7,25 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tracing::{info, error};
-use tracing_subscriber;
+use tracing::{info, warn, error};
 use investengine_csv_server_rs::database::Database;
-use investengine_csv_server_rs::merge_csv::{detect_file_type, FileType, merge_trading_files, merge_cash_files};
+use investengine_csv_server_rs::models::AccountType;
+use investengine_csv_server_rs::merge_csv::{DetectionConfidence, StatementInfo, ParseIssue, ParseMode};
+use investengine_csv_server_rs::statement_parser::ParserRegistry;
 use investengine_csv_server_rs::security_parser::extract_security_and_isin;
-use investengine_csv_server_rs::tickers::search_ticker_for_isin;
-use investengine_csv_server_rs::background_processor::precompute_portfolio_data;
+use investengine_csv_server_rs::tickers::{rank_ticker_candidates, search_ticker_for_isin};
+use investengine_csv_server_rs::price_source::{price_source_from_env, PriceSource};
+use investengine_csv_server_rs::price_fetch::{FetchOutcome, FetchStatus};
+use investengine_csv_server_rs::background_processor::PrecomputeRunner;
+use investengine_csv_server_rs::dedup::MergeSummary;
+use investengine_csv_server_rs::preview::preview_files;
+use investengine_csv_server_rs::cash_activity::summarise_cash_activity;
+use investengine_csv_server_rs::reconciliation::reconcile;
+use investengine_csv_server_rs::period_returns::flow_adjusted_returns;
+use investengine_csv_server_rs::risk::{drawdown_series, first_funded_day, growth_index, rolling_returns, rolling_volatility, ROLLING_WINDOWS};
+use investengine_csv_server_rs::quote_unit::normalise_currency;
+use investengine_csv_server_rs::manual_prices::{parse_manual_prices_csv, ManualPrice};
+use investengine_csv_server_rs::mapping_check::{verify_all_mappings, VerificationStatus};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
 use std::collections::HashMap;
@@ -41,6 +53,9 @@ struct RebalanceTemplate {}
 
 struct AppState {
     db: Arc<Database>,
+    parsers: ParserRegistry,
+    prices: Arc<dyn PriceSource>,
+    precompute: Arc<PrecomputeRunner>,
 }
 
 async fn index_handler() -> impl IntoResponse {
@@ -82,20 +97,41 @@ async fn main() {
         .unwrap_or_else(|_| "mongodb://mongodb:27017/bot_db".to_string());
     info!("Initializing with MongoDB URI: {}", mongo_uri);
     let db = Database::new(&mongo_uri).await.expect("Failed to initialize database");
-    let shared_state = Arc::new(AppState { db: Arc::new(db) });
+    let prices = price_source_from_env().expect("Invalid price source configuration");
+    info!("Using price source: {}", prices.name());
+    let shared_state = Arc::new(AppState {
+        db: Arc::new(db),
+        parsers: ParserRegistry::default(),
+        prices,
+        precompute: Arc::default(),
+    });
 
     let app = Router::new()
         .route("/", get(index_handler))
         .route("/upload/", get(upload_page_handler).post(upload_files_handler))
+        .route("/upload/preview/", post(upload_preview_handler))
         .route("/mappings/", get(mappings_page_handler))
         .route("/rebalance/", get(rebalance_page_handler))
         .route("/reset/", post(reset_database_handler))
         .route("/mapping/", get(get_mappings_handler).post(create_mapping_handler))
         .route("/mapping/missing/", get(get_missing_mappings_handler))
+        .route("/mapping/verify/", get(get_mapping_verifications_handler).post(verify_mappings_handler))
         .route("/mapping/{isin}/", delete(delete_mapping_handler))
+        .route("/mapping/{isin}/candidates/", get(get_mapping_candidates_handler))
+        .route("/manual-prices/", get(get_manual_prices_handler).post(create_manual_prices_handler))
+        .route("/manual-prices/upload/", post(upload_manual_prices_handler))
+        .route("/manual-prices/{key}/{date}/", delete(delete_manual_price_handler))
         .route("/export/prices/", get(export_prices_handler))
         .route("/export/trades/", get(export_trades_handler))
+        .route("/accounts/", get(get_accounts_handler))
+        .route("/cash-activity/", get(get_cash_activity_handler))
+        .route("/reconciliation/", get(get_reconciliation_handler))
         .route("/portfolio-values/", get(get_portfolio_values_handler))
+        .route("/portfolio-values/drawdown/", get(get_drawdown_handler))
+        .route("/portfolio-values/rolling/", get(get_rolling_handler))
+        .route("/data-quality/", get(get_data_quality_handler))
+        .route("/holdings/", get(get_holdings_handler))
+        .route("/period-returns/", get(get_period_returns_handler))
         .route("/rebalance/data/", get(get_rebalance_data_handler))
         .route("/rebalance/calculate/", post(calculate_rebalance_handler))
         .layer(TraceLayer::new_for_http())
@@ -129,6 +165,7 @@ struct RebalanceDataResponse {
 
 async fn get_rebalance_data_handler(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
 ) -> impl IntoResponse {
     let db = &state.db;
 
@@ -151,7 +188,7 @@ async fn get_rebalance_data_handler(
     }
 
     // 2. Get precomputed data
-    let portfolio_data = match db.get_portfolio_values_precomputed().await {
+    let portfolio_data = match db.get_portfolio_values_precomputed(query.account).await {
         Ok(Some(d)) => d,
         Ok(None) => {
             return (StatusCode::NOT_FOUND, Json(serde_json::json!({
@@ -232,7 +269,7 @@ async fn calculate_rebalance_handler(
                 if v.is_string() {
                     v.as_str().and_then(|s| Decimal::from_str(s).ok())
                 } else {
-                    v.as_f64().and_then(|f| Decimal::from_f64(f))
+                    v.as_f64().and_then(Decimal::from_f64)
                 }
             })
         ) {
@@ -277,11 +314,28 @@ struct TradesResponse {
     trades: Vec<investengine_csv_server_rs::models::TradingRecord>,
 }
 
+#[derive(Deserialize)]
+struct AccountQuery {
+    #[serde(default, deserialize_with = "deserialize_account")]
+    account: Option<AccountType>,
+}
+
+/// Rejects unknown names, which `AccountType` itself would read as `Other`.
+fn deserialize_account<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<AccountType>, D::Error> {
+    let Some(name) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    AccountType::from_name(&name)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("Unknown account '{}'", name)))
+}
+
 async fn export_trades_handler(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
 ) -> impl IntoResponse {
     let db = &state.db;
-    match db.load_trades().await {
+    match db.load_trades_for_account(query.account).await {
         Ok(trades) => {
             if trades.is_empty() {
                 return (StatusCode::NOT_FOUND, Json(serde_json::json!({
@@ -304,46 +358,276 @@ async fn export_trades_handler(
     }
 }
 
-async fn get_portfolio_values_handler(
+#[derive(Serialize)]
+struct AccountSummary {
+    account: AccountType,
+    tax_sheltered: bool,
+    annual_allowance: Option<Decimal>,
+    trades: usize,
+    cash_flows: usize,
+}
+
+async fn get_accounts_handler(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
     let db = &state.db;
+    let (trades, cash_flows) = match (db.load_trades().await, db.load_cash_flows().await) {
+        (Ok(t), Ok(c)) => (t, c),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Error loading accounts: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Error loading accounts: {}", e)
+            }))).into_response();
+        }
+    };
 
-    // 1. Validate that all ISINs have ticker mappings
-    match db.get_isins_without_mappings().await {
-        Ok(missing) if !missing.is_empty() => {
+    let accounts: Vec<AccountSummary> = AccountType::ALL.iter()
+        .map(|&account| AccountSummary {
+            account,
+            tax_sheltered: account.is_tax_sheltered(),
+            annual_allowance: account.annual_allowance(),
+            trades: trades.iter().filter(|t| t.account_type == account).count(),
+            cash_flows: cash_flows.iter().filter(|c| c.account_type == account).count(),
+        })
+        .filter(|a| a.trades > 0 || a.cash_flows > 0)
+        .collect();
+
+    Json(serde_json::json!({
+        "success": true,
+        "accounts": accounts,
+    })).into_response()
+}
+
+async fn get_cash_activity_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    let db = &state.db;
+    match db.load_cash_flows_for_account(query.account).await {
+        Ok(records) => {
+            let summary = summarise_cash_activity(&records);
+            Json(serde_json::json!({
+                "success": true,
+                "totals": summary.totals,
+                "rows": summary.rows,
+                "by_year": summary.by_year,
+            })).into_response()
+        }
+        Err(e) => {
+            error!("Error loading cash activity: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Error loading cash activity: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+async fn get_reconciliation_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    let db = &state.db;
+    let loaded = (
+        db.load_cash_flows_for_account(query.account).await,
+        db.load_trades_for_account(query.account).await,
+    );
+    let (cash_flows, trades) = match loaded {
+        (Ok(c), Ok(t)) => (c, t),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Error loading data for reconciliation: {}", e);
             return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
                 "success": false,
-                "error": "Cannot calculate portfolio: missing ticker mappings for ISINs",
-                "missing_isins": missing
+                "error": format!("Error loading data for reconciliation: {}", e)
             }))).into_response();
         }
+    };
+
+    let accounts = reconcile(&cash_flows, &trades);
+    Json(serde_json::json!({
+        "success": true,
+        "clean": accounts.iter().all(|a| a.is_clean()),
+        "accounts": accounts,
+    })).into_response()
+}
+
+#[derive(Deserialize)]
+struct ManualPriceQuery {
+    key: Option<String>,
+}
+
+async fn get_manual_prices_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ManualPriceQuery>,
+) -> impl IntoResponse {
+    let db = &state.db;
+    match db.load_manual_prices(query.key.as_deref()).await {
+        Ok(prices) => Json(serde_json::json!({
+            "success": true,
+            "prices": prices,
+        })).into_response(),
+        Err(e) => {
+            error!("Error loading manual prices: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Error loading manual prices: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+/// Stores manual prices and re-runs the precompute so they show up in valuations.
+///
+/// Prices that cannot be expressed in the currency the security is already quoted in are
+/// rejected rather than left to replace its provider history.
+async fn store_manual_prices(state: &Arc<AppState>, prices: Vec<ManualPrice>, issues: Vec<ParseIssue>) -> axum::response::Response {
+    let mut quoted_in: HashMap<String, Option<String>> = HashMap::new();
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for price in prices {
+        if !quoted_in.contains_key(&price.key) {
+            let currency = match state.db.get_quote_currency(&price.key).await {
+                Ok(currency) => currency,
+                Err(e) => {
+                    error!("Error looking up the currency of {}: {}", price.key, e);
+                    return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                        "success": false,
+                        "error": format!("Failed to check manual prices: {}", e)
+                    }))).into_response();
+                }
+            };
+            quoted_in.insert(price.key.clone(), currency);
+        }
+        let check = match &quoted_in[&price.key] {
+            Some(currency) => price.check_currency(currency),
+            None => Ok(()),
+        };
+        match check {
+            Ok(()) => accepted.push(price),
+            Err(reason) => rejected.push(serde_json::json!({
+                "key": &price.key,
+                "date": price.date,
+                "currency": &price.currency,
+                "reason": reason,
+            })),
+        }
+    }
+    let prices = accepted;
+
+    if prices.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "No valid manual prices supplied",
+            "parse_issues": issues,
+            "rejected": rejected,
+        }))).into_response();
+    }
+
+    match state.db.save_manual_prices(&prices).await {
+        Ok(saved) => {
+            state.precompute.request(&state.db, &state.prices);
+            Json(serde_json::json!({
+                "success": true,
+                "message": format!("Saved {} manual price(s). Background processing started.", saved),
+                "saved": saved,
+                "parse_issues": issues,
+                "rejected": rejected,
+            })).into_response()
+        }
         Err(e) => {
-            error!("Error checking mappings: {}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json(GenericResponse {
+            error!("Error saving manual prices: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to save manual prices: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+async fn create_manual_prices_handler(
+    State(state): State<Arc<AppState>>,
+    Json(entries): Json<Vec<ManualPrice>>,
+) -> impl IntoResponse {
+    let mut prices = Vec::new();
+    let mut issues = Vec::new();
+    for (idx, entry) in entries.into_iter().enumerate() {
+        match entry.validated() {
+            Ok(p) => prices.push(p),
+            Err(reason) => issues.push(ParseIssue {
+                file: "request".to_string(),
+                line: idx as u64 + 1,
+                column: None,
+                raw_value: None,
+                reason,
+            }),
+        }
+    }
+    store_manual_prices(&state, prices, issues).await
+}
+
+async fn upload_manual_prices_handler(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut prices = Vec::new();
+    let mut issues = Vec::new();
+    for (filename, content) in read_csv_uploads(&mut multipart).await {
+        let (parsed, file_issues) = parse_manual_prices_csv(&filename, &content);
+        prices.extend(parsed);
+        issues.extend(file_issues);
+    }
+    store_manual_prices(&state, prices, issues).await
+}
+
+async fn delete_manual_price_handler(
+    State(state): State<Arc<AppState>>,
+    Path((key, date)): Path<(String, chrono::NaiveDate)>,
+) -> impl IntoResponse {
+    let db = &state.db;
+    match db.delete_manual_price(&key, date).await {
+        Ok(true) => {
+            state.precompute.request(&state.db, &state.prices);
+            Json(GenericResponse {
+                success: true,
+                message: format!("Manual price for {} on {} deleted", key, date),
+            }).into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, Json(GenericResponse {
+            success: false,
+            message: format!("No manual price for {} on {}", key, date),
+        })).into_response(),
+        Err(e) => {
+            error!("Error deleting manual price for {} on {}: {}", key, date, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(GenericResponse {
                 success: false,
-                message: format!("Failed to check mappings: {}", e),
-            })).into_response();
+                message: format!("Failed to delete manual price: {}", e),
+            })).into_response()
         }
-        _ => {}
     }
+}
 
-    // 2. Try to get precomputed data first
-    let mut data = match db.get_portfolio_values_precomputed().await {
+async fn get_portfolio_values_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    let db = &state.db;
+
+    // 1. Try to get precomputed data first
+    let mut data = match db.get_portfolio_values_precomputed(query.account).await {
         Ok(Some(d)) => d,
         Ok(None) => {
             // No precomputed data yet
             // Check if there are even trades
-            match db.has_trades_data().await {
+            let has_trades = match query.account {
+                Some(account) => db.load_trades_for_account(Some(account)).await.map(|t| !t.is_empty()),
+                None => db.has_trades_data().await,
+            };
+            match has_trades {
                 Ok(true) => {
                     // Trades exist, but no precomputed data. Trigger it and return error/in_progress
                     info!("No precomputed data but trades exist. Triggering precomputation...");
-                    let db_arc = Arc::clone(&state.db);
-                    tokio::spawn(async move {
-                        if let Err(e) = precompute_portfolio_data(db_arc).await {
-                            error!("Background precomputation failed: {}", e);
-                        }
-                    });
+                    state.precompute.start_if_idle(&state.db, &state.prices);
 
                     return (StatusCode::ACCEPTED, Json(serde_json::json!({
                         "success": true,
@@ -369,7 +653,7 @@ async fn get_portfolio_values_handler(
         }
     };
 
-    // 3. Check if precomputed data is up to date
+    // 2. Check if precomputed data is up to date
     let status = match db.get_precompute_status().await {
         Ok(s) => s,
         Err(_) => serde_json::json!({}),
@@ -389,12 +673,7 @@ async fn get_portfolio_values_handler(
 
     if !is_up_to_date && status.get("status").and_then(|s| s.as_str()) != Some("in_progress") {
         info!("Portfolio data not up to date, triggering background precomputation...");
-        let db_arc = Arc::clone(&state.db);
-        tokio::spawn(async move {
-            if let Err(e) = precompute_portfolio_data(db_arc).await {
-                error!("Background precomputation failed: {}", e);
-            }
-        });
+        state.precompute.start_if_idle(&state.db, &state.prices);
 
         if let Some(obj) = data.as_object_mut() {
             obj.insert("data_extended".to_string(), serde_json::json!(true));
@@ -407,6 +686,55 @@ async fn get_portfolio_values_handler(
         }
     }
 
+    // Holdings valued from manual or our own trade prices are not valued at zero, even
+    // when their fetch failed
+    let sources = status.get("valuation_sources").and_then(|s| s.as_object());
+    let valued_from = |source: &str| -> Vec<&String> {
+        sources.into_iter()
+            .flatten()
+            .filter(|(_, s)| s.as_str() == Some(source))
+            .map(|(ticker, _)| ticker)
+            .collect()
+    };
+    let trade_priced = valued_from("trade_price");
+    let manual_priced = valued_from("manual");
+
+    // Tickers whose prices could not be fetched are valued at zero, so say which ones
+    match db.get_last_fetch_outcomes().await {
+        Ok(outcomes) => {
+            let failed: Vec<&FetchOutcome> = outcomes.iter()
+                .filter(|o| o.status == FetchStatus::Failed)
+                .filter(|o| !trade_priced.contains(&&o.ticker) && !manual_priced.contains(&&o.ticker))
+                .collect();
+            let stale: Vec<&str> = outcomes.iter()
+                .filter(|o| o.status == FetchStatus::Stale)
+                .map(|o| o.ticker.as_str())
+                .collect();
+            if let Some(obj) = data.as_object_mut() {
+                obj.insert("failed_tickers".to_string(), serde_json::json!(failed));
+                obj.insert("stale_tickers".to_string(), serde_json::json!(stale));
+            }
+        }
+        Err(e) => error!("Error loading price fetch outcomes: {}", e),
+    }
+
+    if let (Some(sources), Some(obj)) = (sources, data.as_object_mut()) {
+        obj.insert("valuation_sources".to_string(), serde_json::json!(sources));
+        // Trade prices are approximate
+        obj.insert("trade_priced_tickers".to_string(), serde_json::json!(trade_priced));
+        obj.insert("manual_priced_tickers".to_string(), serde_json::json!(manual_priced));
+    }
+
+    // Unmapped ISINs are valued from their trade prices under the ISIN itself
+    match db.get_isins_without_mappings().await {
+        Ok(missing) => {
+            if let Some(obj) = data.as_object_mut() {
+                obj.insert("missing_isins".to_string(), serde_json::json!(missing));
+            }
+        }
+        Err(e) => error!("Error checking mappings: {}", e),
+    }
+
     if let Some(obj) = data.as_object_mut() {
         obj.insert("success".to_string(), serde_json::json!(true));
     }
@@ -414,6 +742,168 @@ async fn get_portfolio_values_handler(
     Json(data).into_response()
 }
 
+async fn get_holdings_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    match state.db.get_holding_metrics(query.account).await {
+        Ok(holdings) if holdings.is_empty() => (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "No precomputed holdings. Please wait for processing."
+        }))).into_response(),
+        Ok(holdings) => Json(serde_json::json!({
+            "success": true,
+            "holdings": holdings,
+        })).into_response(),
+        Err(e) => {
+            error!("Error loading holding metrics: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to load holding metrics: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+/// The flow-adjusted daily returns of a view, with its dates and first funded day.
+async fn load_daily_returns(db: &Database, account: Option<AccountType>) -> Result<(Vec<String>, Vec<f64>, usize), axum::response::Response> {
+    match db.get_daily_values_and_flows(account).await {
+        Ok((dates, _, _)) if dates.is_empty() => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "No precomputed data. Please wait for processing."
+        }))).into_response()),
+        Ok((dates, values, flows)) => {
+            let start = first_funded_day(&values).unwrap_or(dates.len() - 1);
+            let dates = dates.iter().map(|d| d.to_string()).collect();
+            Ok((dates, flow_adjusted_returns(&values, &flows), start))
+        }
+        Err(e) => {
+            error!("Error loading daily values: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to load daily values: {}", e)
+            }))).into_response())
+        }
+    }
+}
+
+/// How far below its previous high the flow-adjusted index is on each of `daily_dates`.
+async fn get_drawdown_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    let (daily_dates, returns, _) = match load_daily_returns(&state.db, query.account).await {
+        Ok(loaded) => loaded,
+        Err(response) => return response,
+    };
+    Json(serde_json::json!({
+        "success": true,
+        "account": query.account,
+        "daily_dates": daily_dates,
+        "drawdown": drawdown_series(&growth_index(&returns)),
+    })).into_response()
+}
+
+/// Rolling returns and annualised volatility over each window, keyed by its length in days.
+async fn get_rolling_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    let (daily_dates, returns, start) = match load_daily_returns(&state.db, query.account).await {
+        Ok(loaded) => loaded,
+        Err(response) => return response,
+    };
+    let index = growth_index(&returns);
+    let mut rolling_return = serde_json::Map::new();
+    let mut rolling_vol = serde_json::Map::new();
+    for window in ROLLING_WINDOWS {
+        rolling_return.insert(window.to_string(), serde_json::json!(rolling_returns(&index, start, window)));
+        rolling_vol.insert(window.to_string(), serde_json::json!(rolling_volatility(&returns, start, window)));
+    }
+    Json(serde_json::json!({
+        "success": true,
+        "account": query.account,
+        "daily_dates": daily_dates,
+        "rolling_returns": rolling_return,
+        "rolling_volatility": rolling_vol,
+    })).into_response()
+}
+
+async fn get_period_returns_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
+) -> impl IntoResponse {
+    match state.db.get_period_returns(query.account).await {
+        Ok(periods) if periods.is_empty() => (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "No precomputed period returns. Please wait for processing."
+        }))).into_response(),
+        Ok(periods) => Json(serde_json::json!({
+            "success": true,
+            "account": query.account,
+            "periods": periods,
+        })).into_response(),
+        Err(e) => {
+            error!("Error loading period returns: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to load period returns: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DataQualityQuery {
+    /// Adds the day-by-day price provenance for this ticker
+    ticker: Option<String>,
+}
+
+async fn get_data_quality_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DataQualityQuery>,
+) -> impl IntoResponse {
+    let db = &state.db;
+    let (quality, generated_at) = match db.load_data_quality().await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            error!("Error loading data quality: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to load data quality: {}", e)
+            }))).into_response();
+        }
+    };
+
+    let (fx, securities): (Vec<_>, Vec<_>) = quality.into_iter()
+        .filter(|q| query.ticker.as_ref().is_none_or(|t| *t == q.ticker))
+        .partition(|q| q.is_fx);
+    let summary = serde_json::json!({
+        "securities": securities.len(),
+        "with_gaps": securities.iter().filter(|q| !q.gaps.is_empty()).count(),
+        "with_stale_spans": securities.iter().filter(|q| !q.stale_spans.is_empty()).count(),
+        "with_jumps": securities.iter().filter(|q| !q.jumps.is_empty()).count(),
+        "stale_days": securities.iter().flat_map(|q| &q.stale_spans).map(|s| s.days).sum::<usize>(),
+        "missing_fx_days": fx.iter().map(|q| q.missing_days).sum::<usize>(),
+        "stale_fx_days": fx.iter().flat_map(|q| &q.stale_spans).map(|s| s.days).sum::<usize>(),
+    });
+
+    let mut response = serde_json::json!({
+        "success": true,
+        "generated_at": generated_at,
+        "summary": summary,
+        "securities": securities,
+        "fx": fx,
+    });
+    if let Some(ticker) = query.ticker {
+        match db.get_price_provenance(&ticker).await {
+            Ok(days) => response["days"] = serde_json::json!(days),
+            Err(e) => error!("Error loading price provenance for {}: {}", ticker, e),
+        }
+    }
+    Json(response).into_response()
+}
+
 async fn reset_database_handler(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
@@ -455,6 +945,8 @@ struct MappingUpdate {
     isin: String,
     ticker: String,
     security_name: Option<String>,
+    /// Unit the ticker is quoted in, e.g. GBP, GBX or USD; detected when omitted
+    quote_unit: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -507,7 +999,7 @@ async fn create_mapping_handler(
         if !isin_regex.is_match(&isin) {
             results.push(MappingResult {
                 success: false,
-                isin: isin,
+                isin,
                 ticker: None,
                 message: None,
                 error: Some("Invalid ISIN format".to_string()),
@@ -515,11 +1007,25 @@ async fn create_mapping_handler(
             continue;
         }
 
-        match db.save_isin_ticker_mapping(&update.isin, &update.ticker, update.security_name.as_deref()).await {
+        let quote_unit = update.quote_unit.as_deref()
+            .filter(|u| !u.trim().is_empty())
+            .map(normalise_currency);
+        if quote_unit.as_ref().is_some_and(|u| u.len() != 3 || !u.chars().all(|c| c.is_ascii_alphabetic())) {
+            results.push(MappingResult {
+                success: false,
+                isin,
+                ticker: None,
+                message: None,
+                error: Some("Invalid quote unit".to_string()),
+            });
+            continue;
+        }
+
+        match db.save_isin_ticker_mapping(&update.isin, &update.ticker, update.security_name.as_deref(), quote_unit.as_deref()).await {
             Ok(_) => {
                 results.push(MappingResult {
                     success: true,
-                    isin: isin,
+                    isin,
                     ticker: Some(update.ticker),
                     message: Some("Mapping created/updated successfully".to_string()),
                     error: None,
@@ -529,7 +1035,7 @@ async fn create_mapping_handler(
                 error!("Error saving mapping for {}: {}", update.isin, e);
                 results.push(MappingResult {
                     success: false,
-                    isin: isin,
+                    isin,
                     ticker: None,
                     message: None,
                     error: Some(e.to_string()),
@@ -564,6 +1070,88 @@ async fn get_missing_mappings_handler(
     }
 }
 
+/// Results of the last verification, which runs after every precompute.
+async fn get_mapping_verifications_handler(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.db.load_mapping_verifications().await {
+        Ok((results, checked_at)) => {
+            let suspect = results.iter().filter(|r| r.status == VerificationStatus::Suspect).count();
+            Json(serde_json::json!({
+                "success": true,
+                "checked_at": checked_at,
+                "suspect": suspect,
+                "results": results,
+            })).into_response()
+        }
+        Err(e) => {
+            error!("Error loading mapping verifications: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to load mapping verifications: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+/// Re-checks every mapping against its trade prices now.
+async fn verify_mappings_handler(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match verify_all_mappings(&state.db, state.prices.as_ref()).await {
+        Ok(results) => {
+            let suspect = results.iter().filter(|r| r.status == VerificationStatus::Suspect).count();
+            Json(serde_json::json!({
+                "success": true,
+                "checked_at": chrono::Utc::now().to_rfc3339(),
+                "suspect": suspect,
+                "results": results,
+            })).into_response()
+        }
+        Err(e) => {
+            error!("Error verifying mappings: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Mapping verification failed: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CandidatesQuery {
+    name: Option<String>,
+}
+
+async fn get_mapping_candidates_handler(
+    State(state): State<Arc<AppState>>,
+    Path(isin): Path<String>,
+    Query(query): Query<CandidatesQuery>,
+) -> impl IntoResponse {
+    let db = &state.db;
+    // Fall back to the name stored with an existing mapping
+    let name = match query.name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => name,
+        None => db.get_security_name_for_isin(&isin).await.ok().flatten().unwrap_or_default(),
+    };
+
+    match rank_ticker_candidates(state.prices.as_ref(), &name, &isin).await {
+        Ok(candidates) => Json(serde_json::json!({
+            "success": true,
+            "isin": isin,
+            "security_name": name,
+            "candidates": candidates,
+        })).into_response(),
+        Err(e) => {
+            error!("Error searching candidates for {}: {}", isin, e);
+            (StatusCode::BAD_GATEWAY, Json(serde_json::json!({
+                "success": false,
+                "error": format!("Ticker search failed: {}", e)
+            }))).into_response()
+        }
+    }
+}
+
 async fn delete_mapping_handler(
     State(state): State<Arc<AppState>>,
     Path(isin): Path<String>,
@@ -594,11 +1182,12 @@ async fn delete_mapping_handler(
 
 async fn export_prices_handler(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountQuery>,
 ) -> impl IntoResponse {
     let db = &state.db;
     
     // 1. Get current precomputed data
-    let mut data = match db.get_all_precomputed_data().await {
+    let mut data = match db.get_all_precomputed_data(query.account).await {
         Ok(d) => d,
         Err(e) => {
             error!("Error retrieving precomputed data: {}", e);
@@ -627,12 +1216,7 @@ async fn export_prices_handler(
 
     if !is_up_to_date && status.get("status").and_then(|s| s.as_str()) != Some("in_progress") {
         info!("Data not up to date, triggering background precomputation...");
-        let db_arc = Arc::clone(&state.db);
-        tokio::spawn(async move {
-            if let Err(e) = precompute_portfolio_data(db_arc).await {
-                error!("Background precomputation failed: {}", e);
-            }
-        });
+        state.precompute.start_if_idle(&state.db, &state.prices);
 
         // Add extra info to response
         if let Some(obj) = data.as_object_mut() {
@@ -660,41 +1244,42 @@ struct UploadResponse {
     total_cash_flows: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     missing_isins: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trades: Option<MergeSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cash_flows: Option<MergeSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    parse_issues: Vec<ParseIssue>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    files: Vec<DetectedFile>,
 }
 
-async fn upload_files_handler(
-    State(state): State<Arc<AppState>>,
-    mut multipart: Multipart,
-) -> impl IntoResponse {
-    info!("Endpoint /upload/ called");
-
-    let db = &state.db;
+#[derive(Serialize)]
+struct DetectedFile {
+    filename: String,
+    broker: &'static str,
+    #[serde(flatten)]
+    info: StatementInfo,
+}
 
-    // Check if database has existing data
-    match db.has_trades_data().await {
-        Ok(true) => {
-            return (StatusCode::BAD_REQUEST, Json(UploadResponse {
-                success: false,
-                message: "Database contains existing data. Please call /reset/ first.".to_string(),
-                total_trading_transactions: 0,
-                total_cash_flows: 0,
-                missing_isins: None,
-            })).into_response();
-        }
-        Err(e) => {
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse {
-                success: false,
-                message: format!("Database error: {}", e),
-                total_trading_transactions: 0,
-                total_cash_flows: 0,
-                missing_isins: None,
-            })).into_response();
+impl UploadResponse {
+    fn failure(message: String) -> Self {
+        Self {
+            success: false,
+            message,
+            total_trading_transactions: 0,
+            total_cash_flows: 0,
+            missing_isins: None,
+            trades: None,
+            cash_flows: None,
+            parse_issues: Vec::new(),
+            files: Vec::new(),
         }
-        _ => {}
     }
+}
 
-    let mut trading_files = Vec::new();
-    let mut cash_files = Vec::new();
+async fn read_csv_uploads(multipart: &mut Multipart) -> Vec<(String, String)> {
+    let mut files = Vec::new();
 
     while let Ok(Some(field)) = multipart.next_field().await {
         let filename = field.file_name().unwrap_or_default().to_string();
@@ -705,152 +1290,235 @@ async fn upload_files_handler(
 
         let data = field.bytes().await.unwrap_or_default();
         let content = String::from_utf8_lossy(&data).to_string();
+        files.push((filename, content));
+    }
+
+    files
+}
+
+#[derive(Serialize)]
+struct UnmappedIsin {
+    isin: String,
+    security_name: String,
+}
 
-        match detect_file_type(&filename) {
-            FileType::Trading => trading_files.push((filename, content)),
-            FileType::Cash => cash_files.push((filename, content)),
+async fn upload_preview_handler(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    info!("Endpoint /upload/preview/ called");
+
+    let db = &state.db;
+    let files = read_csv_uploads(&mut multipart).await;
+
+    if files.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "No valid CSV files uploaded"
+        }))).into_response();
+    }
+
+    let preview = preview_files(&state.parsers, files);
+
+    // Only read existing mappings here; a preview must never search or store anything
+    let mut unmapped_isins = Vec::new();
+    for (isin, security_name) in &preview.securities {
+        match db.get_ticker_for_isin(isin).await {
+            Ok(Some(_)) => {}
+            Ok(None) => unmapped_isins.push(UnmappedIsin {
+                isin: isin.clone(),
+                security_name: security_name.clone(),
+            }),
+            Err(e) => {
+                error!("Error checking mapping for {}: {}", isin, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to check mappings: {}", e)
+                }))).into_response();
+            }
         }
     }
 
-    if trading_files.is_empty() && cash_files.is_empty() {
-        return (StatusCode::BAD_REQUEST, Json(UploadResponse {
-            success: false,
-            message: "No valid CSV files uploaded".to_string(),
-            total_trading_transactions: 0,
-            total_cash_flows: 0,
-            missing_isins: None,
-        })).into_response();
+    Json(serde_json::json!({
+        "success": true,
+        "files": preview.files,
+        "total_trading_transactions": preview.total_trading_transactions,
+        "total_cash_flows": preview.total_cash_flows,
+        "date_from": preview.date_from,
+        "date_to": preview.date_to,
+        "unmapped_isins": unmapped_isins,
+    })).into_response()
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    mode: Option<ParseMode>,
+}
+
+async fn upload_files_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UploadQuery>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    info!("Endpoint /upload/ called");
+
+    let db = &state.db;
+    let mode = query.mode.unwrap_or_else(ParseMode::from_env);
+
+    let mut detected_files = Vec::new();
+    let mut trading_records = Vec::new();
+    let mut all_cash_records = Vec::new();
+    let mut parse_issues = Vec::new();
+
+    // Parse everything up front so all row problems are reported together
+    for (filename, content) in read_csv_uploads(&mut multipart).await {
+        let Some((broker, info, parsed)) = state.parsers.parse(&filename, &content) else {
+            continue;
+        };
+        if info.confidence == DetectionConfidence::Low {
+            warn!("Classified {} from its filename only ({:?}, {})", filename, info.file_type, info.account_type);
+        }
+        trading_records.extend(parsed.trades);
+        all_cash_records.extend(parsed.cash);
+        parse_issues.extend(parsed.issues);
+        detected_files.push(DetectedFile { filename, broker, info });
+    }
+
+    if detected_files.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(UploadResponse::failure(
+            "No valid CSV files uploaded".to_string(),
+        ))).into_response();
     }
 
+    if mode == ParseMode::Strict && !parse_issues.is_empty() {
+        let mut response = UploadResponse::failure(format!(
+            "{} row(s) failed to parse. Nothing was stored; fix the files or upload in lenient mode.",
+            parse_issues.len()
+        ));
+        response.parse_issues = parse_issues;
+        response.files = detected_files;
+        return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+    }
+
+    trading_records.sort_by_key(|r| r.trade_date_time);
+    all_cash_records.sort_by_key(|r| r.date);
     let mut all_trading_records = Vec::new();
-    let mut all_cash_records = Vec::new();
+    // Stored without a ticker; precompute values them from their trade prices
+    let mut unmapped_isins = Vec::new();
 
     // Process trading files
-    if !trading_files.is_empty() {
-        match merge_trading_files(trading_files) {
-            Ok(records) => {
-                let mut missing_isins = Vec::new();
-                let mut processed_records = records;
-
-                // 1. Normalize ISINs first
-                for record in &mut processed_records {
-                    let (_name, isin_opt) = extract_security_and_isin(&record.security_isin);
-                    record.security_isin = isin_opt.unwrap_or_default();
-                }
-
-                // 2. Identify unique ISINs that need mapping
-                let unique_isins: std::collections::HashSet<String> = processed_records.iter()
-                    .map(|r| r.security_isin.clone())
-                    .filter(|s| !s.is_empty())
-                    .collect();
+    if !trading_records.is_empty() {
+        let mut missing_isins = Vec::new();
+        let mut processed_records = trading_records;
+
+        // 1. Normalize ISINs first, keeping the names to search with
+        let mut security_names = std::collections::HashMap::new();
+        for record in &mut processed_records {
+            let (name, isin_opt) = extract_security_and_isin(&record.security_isin);
+            record.security_isin = isin_opt.unwrap_or_default();
+            security_names.entry(record.security_isin.clone()).or_insert(name);
+        }
 
-                // 3. Check existing mappings and search for missing ones once per ISIN
-                let mut mapping_cache = std::collections::HashMap::new();
-                for isin in unique_isins {
-                    match db.get_ticker_for_isin(&isin).await {
+        // 2. Identify unique ISINs that need mapping
+        let unique_isins: std::collections::HashSet<String> = processed_records.iter()
+            .map(|r| r.security_isin.clone())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        // 3. Check existing mappings and search for missing ones once per ISIN
+        let mut mapping_cache = std::collections::HashMap::new();
+        for isin in unique_isins {
+            match db.get_ticker_for_isin(&isin).await {
+                Ok(Some(ticker)) => {
+                    mapping_cache.insert(isin, Some(ticker));
+                }
+                Ok(None) => {
+                    info!("Searching ticker for ISIN: {}", isin);
+                    let name = security_names.get(&isin).map(|n| n.as_str()).unwrap_or_default();
+                    match search_ticker_for_isin(state.prices.as_ref(), name, &isin).await {
                         Ok(Some(ticker)) => {
+                            let security_name = Some(name).filter(|n| !n.is_empty());
+                            db.save_isin_ticker_mapping(&isin, &ticker, security_name, None).await.unwrap_or_default();
                             mapping_cache.insert(isin, Some(ticker));
                         }
-                        Ok(None) => {
-                            info!("Searching ticker for ISIN: {}", isin);
-                            match search_ticker_for_isin("", &isin).await {
-                                Ok(Some(ticker)) => {
-                                    db.save_isin_ticker_mapping(&isin, &ticker, None).await.unwrap_or_default();
-                                    mapping_cache.insert(isin, Some(ticker));
-                                }
-                                _ => {
-                                    mapping_cache.insert(isin.clone(), None);
-                                    missing_isins.push(isin);
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            mapping_cache.insert(isin, None);
+                        _ => {
+                            mapping_cache.insert(isin.clone(), None);
+                            missing_isins.push(isin);
                         }
                     }
                 }
-
-                // 4. Assign tickers to records
-                for record in &mut processed_records {
-                    if let Some(Some(ticker)) = mapping_cache.get(&record.security_isin) {
-                        record.ticker = Some(ticker.clone());
-                    }
-                }
-
-                if !missing_isins.is_empty() {
-                    return (StatusCode::BAD_REQUEST, Json(UploadResponse {
-                        success: false,
-                        message: "Missing ticker mappings for some ISINs".to_string(),
-                        total_trading_transactions: 0,
-                        total_cash_flows: 0,
-                        missing_isins: Some(missing_isins),
-                    })).into_response();
+                Err(_) => {
+                    mapping_cache.insert(isin, None);
                 }
-
-                all_trading_records = processed_records;
-            }
-            Err(e) => {
-                return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse {
-                    success: false,
-                    message: format!("Failed to process trading files: {}", e),
-                    total_trading_transactions: 0,
-                    total_cash_flows: 0,
-                    missing_isins: None,
-                })).into_response();
             }
         }
-    }
 
-    // Process cash files
-    if !cash_files.is_empty() {
-        match merge_cash_files(cash_files) {
-            Ok(records) => all_cash_records = records,
-            Err(e) => {
-                return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse {
-                    success: false,
-                    message: format!("Failed to process cash files: {}", e),
-                    total_trading_transactions: 0,
-                    total_cash_flows: 0,
-                    missing_isins: None,
-                })).into_response();
+        // 4. Assign tickers to records
+        for record in &mut processed_records {
+            if let Some(Some(ticker)) = mapping_cache.get(&record.security_isin) {
+                record.ticker = Some(ticker.clone());
             }
         }
-    }
 
-    // Save to database
-    if let Err(e) = db.save_trades(&all_trading_records).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse {
-            success: false,
-            message: format!("Failed to save trades: {}", e),
-            total_trading_transactions: 0,
-            total_cash_flows: 0,
-            missing_isins: None,
-        })).into_response();
+        all_trading_records = processed_records;
+        missing_isins.sort();
+        unmapped_isins = missing_isins;
     }
 
-    if let Err(e) = db.save_cash_flows(&all_cash_records).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse {
-            success: false,
-            message: format!("Failed to save cash flows: {}", e),
-            total_trading_transactions: 0,
-            total_cash_flows: 0,
-            missing_isins: None,
-        })).into_response();
-    }
+    // Append to database, skipping rows that earlier uploads already stored
+    let trades_summary = match db.save_trades(&all_trading_records).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse::failure(
+                format!("Failed to save trades: {}", e),
+            ))).into_response();
+        }
+    };
 
-    // Trigger background precomputation
-    let db_arc = Arc::clone(&state.db);
-    tokio::spawn(async move {
-        if let Err(e) = precompute_portfolio_data(db_arc).await {
-            error!("Background precomputation failed: {}", e);
+    let cash_summary = match db.save_cash_flows(&all_cash_records).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(UploadResponse::failure(
+                format!("Failed to save cash flows: {}", e),
+            ))).into_response();
         }
-    });
+    };
+
+    // Trigger background precomputation only if something actually changed
+    let inserted = trades_summary.inserted + cash_summary.inserted;
+    if inserted > 0 {
+        state.precompute.request(&state.db, &state.prices);
+    }
+
+    let mut message = format!(
+        "Inserted {} trading transactions and {} cash flows ({} duplicates skipped, {} conflicts).",
+        trades_summary.inserted,
+        cash_summary.inserted,
+        trades_summary.duplicates + cash_summary.duplicates,
+        trades_summary.conflicts + cash_summary.conflicts,
+    );
+    if !parse_issues.is_empty() {
+        message.push_str(&format!(" {} unparseable row(s) were skipped.", parse_issues.len()));
+    }
+    if !unmapped_isins.is_empty() {
+        message.push_str(&format!(
+            " {} ISIN(s) have no ticker mapping and are valued from trade prices until one is added.",
+            unmapped_isins.len()
+        ));
+    }
+    if inserted > 0 {
+        message.push_str(" Background processing started.");
+    }
 
     (StatusCode::OK, Json(UploadResponse {
         success: true,
-        message: format!("Successfully uploaded {} trading transactions and {} cash flows. Background processing started.", all_trading_records.len(), all_cash_records.len()),
+        message,
         total_trading_transactions: all_trading_records.len(),
         total_cash_flows: all_cash_records.len(),
-        missing_isins: None,
+        missing_isins: Some(unmapped_isins).filter(|m| !m.is_empty()),
+        trades: Some(trades_summary),
+        cash_flows: Some(cash_summary),
+        parse_issues,
+        files: detected_files,
     })).into_response()
 }
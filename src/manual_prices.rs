@@ -0,0 +1,246 @@
+use crate::merge_csv::ParseIssue;
+use crate::quote_unit::normalise_currency;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// A user-entered close, e.g. a fund NAV, for one security on one date.
+///
+/// `key` is either the security's ISIN or its provider ticker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManualPrice {
+    pub key: String,
+    pub date: NaiveDate,
+    pub price: Decimal,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+fn default_currency() -> String {
+    "GBP".to_string()
+}
+
+impl ManualPrice {
+    /// Checks the entry and normalises its key and currency.
+    pub fn validated(mut self) -> Result<Self, String> {
+        self.key = self.key.trim().to_string();
+        if self.key.is_empty() {
+            return Err("Key (ISIN or ticker) is required".to_string());
+        }
+        if self.price <= Decimal::ZERO {
+            return Err(format!("Price must be positive, got {}", self.price));
+        }
+        self.currency = normalise_currency(&self.currency);
+        Ok(self)
+    }
+
+    /// Checks the entry can be expressed in `quoted_in`, the currency the security's
+    /// provider prices are quoted in.
+    pub fn check_currency(&self, quoted_in: &str) -> Result<(), String> {
+        match rescale(self.price, &self.currency, quoted_in) {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "{} is quoted in {}, so a price in {} cannot be used for it",
+                self.key, quoted_in, self.currency
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ManualPriceRow {
+    #[serde(rename = "Key", alias = "ISIN", alias = "Ticker")]
+    key: String,
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "Price", alias = "NAV", alias = "Close")]
+    price: String,
+    #[serde(rename = "Currency", default)]
+    currency: Option<String>,
+}
+
+/// Parses an uploaded `Key,Date,Price[,Currency]` file.
+///
+/// `ISIN` or `Ticker` may stand in for `Key`, and `NAV` or `Close` for `Price`. Dates are
+/// ISO or `dd/mm/yyyy`. Bad rows are reported and skipped.
+pub fn parse_manual_prices_csv(filename: &str, content: &str) -> (Vec<ManualPrice>, Vec<ParseIssue>) {
+    let mut prices = Vec::new();
+    let mut issues = Vec::new();
+    let content = content.trim_start_matches('\u{feff}');
+    let mut rdr = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(Cursor::new(content));
+
+    for (idx, result) in rdr.deserialize::<ManualPriceRow>().enumerate() {
+        // Header is line 1
+        let line = idx as u64 + 2;
+        let issue = |column: Option<&str>, raw: Option<&str>, reason: String| ParseIssue {
+            file: filename.to_string(),
+            line,
+            column: column.map(str::to_string),
+            raw_value: raw.map(str::to_string),
+            reason,
+        };
+
+        let row = match result {
+            Ok(row) => row,
+            Err(e) => {
+                issues.push(issue(None, None, e.to_string()));
+                continue;
+            }
+        };
+        let Some(date) = parse_date(&row.date) else {
+            issues.push(issue(Some("Date"), Some(&row.date), "Invalid date".to_string()));
+            continue;
+        };
+        let Ok(price) = Decimal::from_str(&row.price.replace(',', "")) else {
+            issues.push(issue(Some("Price"), Some(&row.price), "Invalid price".to_string()));
+            continue;
+        };
+        let entry = ManualPrice {
+            key: row.key,
+            date,
+            price,
+            currency: row.currency.unwrap_or_default(),
+        };
+        match entry.validated() {
+            Ok(entry) => prices.push(entry),
+            Err(reason) => issues.push(issue(None, None, reason)),
+        }
+    }
+
+    (prices, issues)
+}
+
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(raw, "%d/%m/%Y"))
+        .ok()
+}
+
+/// Overlays manual prices on provider bars.
+///
+/// A manual price replaces the provider bar for its date, provider bars fill every other
+/// date, and later entries in `manual` win over earlier ones for the same date.
+pub fn merge_manual_prices(
+    provider: Vec<(NaiveDate, Decimal, String)>,
+    manual: &[&ManualPrice],
+) -> Vec<(NaiveDate, Decimal, String)> {
+    let mut merged: BTreeMap<NaiveDate, (Decimal, String)> = provider
+        .into_iter()
+        .map(|(date, price, currency)| (date, (price, currency)))
+        .collect();
+    for m in manual {
+        merged.insert(m.date, (m.price, m.currency.clone()));
+    }
+    merged.into_iter().map(|(date, (price, currency))| (date, price, currency)).collect()
+}
+
+/// Expresses `price` in `to`, which only works between pounds and pence.
+fn rescale(price: Decimal, from: &str, to: &str) -> Option<Decimal> {
+    match (from, to) {
+        _ if from == to => Some(price),
+        ("GBP", "GBp") => Some(price * Decimal::from(100)),
+        ("GBp", "GBP") => Some(price / Decimal::from(100)),
+        _ => None,
+    }
+}
+
+/// The currency a security's series is kept in: that of its latest provider bar, or of its
+/// latest manual price when the provider has none.
+pub fn series_currency(provider: &[(NaiveDate, Decimal, String)], manual: &[&ManualPrice]) -> String {
+    provider.last()
+        .map(|(_, _, c)| c.clone())
+        .or_else(|| manual.iter().max_by_key(|m| m.date).map(|m| m.currency.clone()))
+        .unwrap_or_else(default_currency)
+}
+
+/// Puts a merged series into `currency`.
+///
+/// Returns the (date, price) pairs and the number of bars that had to be dropped because
+/// their currency could not be rescaled.
+pub fn single_currency_series(bars: Vec<(NaiveDate, Decimal, String)>, currency: &str) -> (Vec<(NaiveDate, Decimal)>, usize) {
+    let mut dropped = 0;
+    let series = bars
+        .into_iter()
+        .filter_map(|(date, price, c)| {
+            let rescaled = rescale(price, &c, currency);
+            if rescaled.is_none() {
+                dropped += 1;
+            }
+            rescaled.map(|p| (date, p))
+        })
+        .collect();
+    (series, dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    fn manual(date: NaiveDate, price: Decimal, currency: &str) -> ManualPrice {
+        ManualPrice { key: "GB00B4PQW151".to_string(), date, price, currency: currency.to_string() }
+    }
+
+    #[test]
+    fn test_manual_prices_override_provider_bars() {
+        let provider = vec![
+            (d(2024, 1, 2), dec!(10000), "GBp".to_string()),
+            (d(2024, 1, 3), dec!(10100), "GBp".to_string()),
+        ];
+        // A wrong provider close corrected in pounds, plus a NAV after the provider stops
+        let fix = manual(d(2024, 1, 3), dec!(100.50), "GBP");
+        let nav = manual(d(2024, 1, 31), dec!(103), "GBP");
+
+        assert_eq!(series_currency(&provider, &[&fix, &nav]), "GBp");
+        let merged = merge_manual_prices(provider, &[&fix, &nav]);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1], (d(2024, 1, 3), dec!(100.50), "GBP".to_string()));
+
+        let (series, dropped) = single_currency_series(merged, "GBP");
+        assert_eq!(dropped, 0);
+        assert_eq!(series, vec![(d(2024, 1, 2), dec!(100)), (d(2024, 1, 3), dec!(100.50)), (d(2024, 1, 31), dec!(103))]);
+        assert_eq!(series_currency(&[], &[&nav, &fix]), "GBP");
+    }
+
+    #[test]
+    fn test_manual_price_in_another_currency_keeps_provider_history() {
+        let provider = vec![
+            (d(2024, 1, 2), dec!(120), "USD".to_string()),
+            (d(2024, 1, 3), dec!(121), "USD".to_string()),
+        ];
+        let nav = manual(d(2024, 1, 4), dec!(100), "GBP");
+        assert!(nav.check_currency("USD").is_err());
+        assert!(nav.check_currency("GBp").is_ok());
+
+        let currency = series_currency(&provider, &[&nav]);
+        let (series, dropped) = single_currency_series(merge_manual_prices(provider, &[&nav]), &currency);
+        assert_eq!(currency, "USD");
+        assert_eq!(series, vec![(d(2024, 1, 2), dec!(120)), (d(2024, 1, 3), dec!(121))]);
+        assert_eq!(dropped, 1);
+    }
+
+    #[test]
+    fn test_parse_manual_prices_csv() {
+        let content = "ISIN,Date,NAV,Currency\n\
+                       GB00B4PQW151,2024-01-31,103.20,GBP\n\
+                       GB00B4PQW151,29/02/2024,10450,GBX\n\
+                       GB00B4PQW151,2024-13-01,104,GBP\n\
+                       GB00B4PQW151,2024-03-28,-1,\n";
+        let (prices, issues) = parse_manual_prices_csv("navs.csv", content);
+
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[1].date, d(2024, 2, 29));
+        assert_eq!(prices[1].currency, "GBp");
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].line, 4);
+        assert_eq!(issues[0].column.as_deref(), Some("Date"));
+        assert_eq!(issues[1].line, 5);
+    }
+}
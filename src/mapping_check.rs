@@ -0,0 +1,237 @@
+use crate::database::Database;
+use crate::env::parse_var;
+use crate::models::TradingRecord;
+use crate::price_cache::get_cached_prices;
+use crate::price_source::PriceSource;
+use crate::prices::CurrencyConverter;
+use crate::quote_unit::normalise_currency;
+use crate::statement_parser::STATEMENT_CURRENCY;
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{info, warn};
+
+/// Trades shown per mapping, worst first
+const SAMPLES_KEPT: usize = 5;
+
+/// How far back a provider close may be from the trade date
+const MAX_PRICE_AGE_DAYS: i64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Verified,
+    Suspect,
+    /// No provider price near any trade date
+    Unverifiable,
+}
+
+/// One trade compared with the provider close for its date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeCheck {
+    pub date: NaiveDate,
+    pub trade_price: Decimal,
+    /// Provider close converted into the statement currency
+    pub provider_price: Decimal,
+    /// provider / trade - 1
+    pub deviation: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MappingVerification {
+    pub isin: String,
+    pub ticker: String,
+    pub status: VerificationStatus,
+    pub trades_checked: usize,
+    /// Median of provider / trade - 1 over the checked trades
+    pub median_deviation: Option<f64>,
+    pub hint: Option<String>,
+    pub samples: Vec<TradeCheck>,
+}
+
+/// Compares trade prices with provider prices already in the statement currency.
+///
+/// `provider_price` returns the close for a date, if any. The mapping is suspect when the
+/// median relative deviation exceeds `tolerance`; a median ratio near 100 or 1/100 points
+/// at a pence/pound mix-up rather than a wrong security.
+pub fn verify_mapping(
+    isin: &str,
+    ticker: &str,
+    trades: &[(NaiveDate, Decimal)],
+    provider_price: impl Fn(NaiveDate) -> Option<Decimal>,
+    tolerance: f64,
+) -> MappingVerification {
+    let mut checks: Vec<TradeCheck> = trades.iter()
+        .filter(|(_, price)| *price > Decimal::ZERO)
+        .filter_map(|&(date, trade_price)| {
+            let provider = provider_price(date)?;
+            let deviation = (provider / trade_price).to_f64()? - 1.0;
+            Some(TradeCheck { date, trade_price, provider_price: provider, deviation })
+        })
+        .collect();
+
+    let median_deviation = median(checks.iter().map(|c| c.deviation).collect());
+    let (status, hint) = match median_deviation {
+        None => (VerificationStatus::Unverifiable, Some("No provider prices near the trade dates".to_string())),
+        Some(m) if m.abs() <= tolerance => (VerificationStatus::Verified, None),
+        Some(m) => {
+            let ratio = m + 1.0;
+            let hint = if (80.0..=125.0).contains(&ratio) {
+                "Provider prices are about 100x the traded prices: the quote unit is probably GBX, not GBP"
+            } else if (0.008..=0.0125).contains(&ratio) {
+                "Provider prices are about 1/100 of the traded prices: the quote unit is probably GBP, not GBX"
+            } else {
+                "Provider prices do not match the traded prices: check the listing and share class"
+            };
+            (VerificationStatus::Suspect, Some(hint.to_string()))
+        }
+    };
+
+    let trades_checked = checks.len();
+    checks.sort_by(|a, b| b.deviation.abs().total_cmp(&a.deviation.abs()));
+    checks.truncate(SAMPLES_KEPT);
+
+    MappingVerification {
+        isin: isin.to_string(),
+        ticker: ticker.to_string(),
+        status,
+        trades_checked,
+        median_deviation,
+        hint,
+        samples: checks,
+    }
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] })
+}
+
+/// Relative tolerance from `MAPPING_TOLERANCE` (default 0.05, i.e. 5%).
+pub fn tolerance_from_env() -> f64 {
+    parse_var("MAPPING_TOLERANCE").unwrap_or(0.05)
+}
+
+/// Checks every mapping that has trades and stores the results.
+pub async fn verify_all_mappings(db: &Database, source: &dyn PriceSource) -> Result<Vec<MappingVerification>> {
+    let trades = db.load_trades().await?;
+    let quote_units = db.get_quote_units().await?;
+    // Trade prices are in the statement currency, so compare in that rather than the base
+    let converter = CurrencyConverter::from_env()?.with_base(STATEMENT_CURRENCY);
+    let tolerance = tolerance_from_env();
+
+    let mut by_isin: BTreeMap<(String, String), Vec<&TradingRecord>> = BTreeMap::new();
+    for t in &trades {
+        if let Some(ref ticker) = t.ticker
+            && !t.security_isin.is_empty()
+        {
+            by_isin.entry((t.security_isin.clone(), ticker.clone())).or_default().push(t);
+        }
+    }
+
+    let today = Utc::now().date_naive();
+    // FX legs are shared between securities, so they cover the earliest trade of any of them
+    let fx_start = by_isin.values()
+        .flatten()
+        .map(|t| t.trade_date_time.date())
+        .min()
+        .unwrap_or(today) - Duration::days(MAX_PRICE_AGE_DAYS);
+    let mut fx_cache: HashMap<String, BTreeMap<NaiveDate, Decimal>> = HashMap::new();
+    let mut results = Vec::new();
+    for ((isin, ticker), records) in by_isin {
+        let points: Vec<(NaiveDate, Decimal)> = records.iter()
+            .map(|t| (t.trade_date_time.date(), t.share_price))
+            .collect();
+        let start = points.iter().map(|(d, _)| *d).min().unwrap_or(today) - Duration::days(MAX_PRICE_AGE_DAYS);
+        let end = points.iter().map(|(d, _)| *d).max().unwrap_or(today).min(today);
+
+        let bars = match get_cached_prices(db, source, &ticker, start, end).await {
+            Ok(cached) => cached.bars,
+            Err(e) => {
+                warn!("Cannot verify {} ({}): {}", isin, ticker, e);
+                Vec::new()
+            }
+        };
+        let unit = quote_units.get(&isin).cloned()
+            .or_else(|| bars.last().map(|(_, _, c)| normalise_currency(c)))
+            .unwrap_or_else(|| STATEMENT_CURRENCY.to_string());
+
+        for leg in converter.legs(&unit) {
+            if fx_cache.contains_key(&leg.ticker) {
+                continue;
+            }
+            let series = match get_cached_prices(db, source, &leg.ticker, fx_start, today).await {
+                Ok(cached) => cached.bars.into_iter().map(|(d, p, _)| (d, p)).collect(),
+                Err(e) => {
+                    warn!("No FX rates {} for verifying {}: {}", leg.ticker, isin, e);
+                    BTreeMap::new()
+                }
+            };
+            fx_cache.insert(leg.ticker, series);
+        }
+
+        let closes: BTreeMap<NaiveDate, Decimal> = bars.into_iter().map(|(d, p, _)| (d, p)).collect();
+        let verification = verify_mapping(&isin, &ticker, &points, |date| {
+            let close = latest_on_or_before(&closes, date)?;
+            converter.convert(close, &unit, |leg| latest_on_or_before(fx_cache.get(&leg.ticker)?, date)).ok()
+        }, tolerance);
+        if verification.status == VerificationStatus::Suspect {
+            warn!("Mapping {} -> {} looks wrong: {}", isin, ticker, verification.hint.as_deref().unwrap_or(""));
+        }
+        results.push(verification);
+    }
+
+    info!("Verified {} mappings", results.len());
+    db.save_mapping_verifications(&results).await?;
+    Ok(results)
+}
+
+fn latest_on_or_before(series: &BTreeMap<NaiveDate, Decimal>, date: NaiveDate) -> Option<Decimal> {
+    series.range(date - Duration::days(MAX_PRICE_AGE_DAYS)..=date)
+        .next_back()
+        .map(|(_, p)| *p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    #[test]
+    fn test_verify_mapping() {
+        let trades = vec![(d(2024, 1, 3), dec!(100.20)), (d(2024, 2, 1), dec!(104)), (d(2024, 3, 1), dec!(106))];
+        let closes: BTreeMap<NaiveDate, Decimal> = [
+            (d(2024, 1, 3), dec!(100.50)),
+            (d(2024, 2, 1), dec!(103.80)),
+            (d(2024, 3, 1), dec!(106.30)),
+        ].into_iter().collect();
+
+        let ok = verify_mapping("IE00BK5BQT80", "VWRP.L", &trades, |date| closes.get(&date).copied(), 0.05);
+        assert_eq!(ok.status, VerificationStatus::Verified);
+        assert_eq!(ok.trades_checked, 3);
+
+        // Pence read as pounds
+        let pence = verify_mapping("IE00BK5BQT80", "VWRP.L", &trades, |date| closes.get(&date).map(|p| p * dec!(100)), 0.05);
+        assert_eq!(pence.status, VerificationStatus::Suspect);
+        assert!(pence.hint.unwrap().contains("GBX"));
+
+        // A different share class trading at another price level
+        let other = verify_mapping("IE00BK5BQT80", "VWRA.L", &trades, |date| closes.get(&date).map(|p| p * dec!(1.27)), 0.05);
+        assert_eq!(other.status, VerificationStatus::Suspect);
+        assert!(other.hint.unwrap().contains("share class"));
+
+        let none = verify_mapping("IE00BK5BQT80", "VWRP.L", &trades, |_| None, 0.05);
+        assert_eq!(none.status, VerificationStatus::Unverifiable);
+    }
+}
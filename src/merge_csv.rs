@@ -1,10 +1,21 @@
-use crate::models::{CashRecord, TradingRecord};
-use anyhow::{Context, Result};
+use crate::models::{AccountType, CashActivityKind, CashRecord, TradingRecord, TransactionKind};
+use crate::statement_parser::BrokerRow;
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io::Cursor;
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FileType {
     Trading,
     Cash,
+    /// Trades and cash movements in one export (Trading 212, Freetrade)
+    Combined,
 }
 
 pub fn detect_file_type(filename: &str) -> FileType {
@@ -16,61 +27,356 @@ pub fn detect_file_type(filename: &str) -> FileType {
     }
 }
 
-pub fn extract_account_type(filename: &str) -> String {
+pub fn extract_account_type(filename: &str) -> AccountType {
+    // Underscores and dashes count as word separators in filenames
+    let words = filename.replace(['_', '-', '.'], " ");
+    if let Some(account) = account_type_from_text(&words) {
+        return account;
+    }
+
     let filename_upper = filename.to_uppercase();
-    if filename_upper.starts_with("GIA_") || filename_upper.contains("_GIA_") {
-        "GIA".to_string()
-    } else if filename_upper.starts_with("ISA_") || filename_upper.contains("_ISA_") {
-        "ISA".to_string()
-    } else if filename_upper.contains("GIA") {
-        "GIA".to_string()
+    if filename_upper.contains("GIA") {
+        AccountType::GIA
     } else if filename_upper.contains("ISA") {
-        "ISA".to_string()
+        AccountType::ISA
     } else {
-        "Unknown".to_string()
+        AccountType::Other
     }
 }
 
-pub fn merge_trading_files(file_data: Vec<(String, String)>) -> Result<Vec<TradingRecord>> {
+static PORTFOLIO_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)Portfolio:\s*([^,]+)").unwrap()
+});
+
+static GIA_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bGIA\b|General Investment Account").unwrap()
+});
+
+static ISA_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bISA\b|Stocks (and|&) Shares ISA").unwrap()
+});
+
+static LISA_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bLISA\b|Lifetime ISA").unwrap()
+});
+
+static JISA_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bJISA\b|Junior ISA").unwrap()
+});
+
+static SIPP_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bSIPP\b|Self[- ]Invested Personal Pension|\bPension\b").unwrap()
+});
+
+const TRADING_TITLE: &str = "Transaction Statement:";
+const CASH_TITLE: &str = "Cash Statement:";
+const TRADING_HEADER: &str = "Security / ISIN,";
+const CASH_HEADER: &str = "Date,Activity";
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionConfidence {
+    /// Only the filename matched
+    Low,
+    /// One of title line / header row matched
+    Medium,
+    /// Title line and header row agree
+    High,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementInfo {
+    pub file_type: FileType,
+    pub account_type: AccountType,
+    pub portfolio: Option<String>,
+    pub confidence: DetectionConfidence,
+}
+
+/// Reads the account type from free text such as a statement title line.
+fn account_type_from_text(text: &str) -> Option<AccountType> {
+    // "Lifetime ISA" and "Junior ISA" also match the plain ISA pattern, so check them first
+    if LISA_RE.is_match(text) {
+        Some(AccountType::LISA)
+    } else if JISA_RE.is_match(text) {
+        Some(AccountType::JISA)
+    } else if SIPP_RE.is_match(text) {
+        Some(AccountType::SIPP)
+    } else if GIA_RE.is_match(text) {
+        Some(AccountType::GIA)
+    } else if ISA_RE.is_match(text) {
+        Some(AccountType::ISA)
+    } else {
+        None
+    }
+}
+
+/// Classifies a statement from its title line and header row, falling back to the filename.
+pub fn detect_statement(filename: &str, content: &str) -> StatementInfo {
+    let lines: Vec<&str> = content.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+
+    let title = lines.first().copied().unwrap_or_default();
+    let title_type = if title.starts_with(TRADING_TITLE) {
+        Some(FileType::Trading)
+    } else if title.starts_with(CASH_TITLE) {
+        Some(FileType::Cash)
+    } else {
+        None
+    };
+
+    // Trading headers follow the title directly; cash headers follow each section title
+    let header_type = lines.iter().take(5).find_map(|l| {
+        if l.starts_with(TRADING_HEADER) {
+            Some(FileType::Trading)
+        } else if l.starts_with(CASH_HEADER) {
+            Some(FileType::Cash)
+        } else {
+            None
+        }
+    });
+
+    let (file_type, mut confidence) = match (title_type, header_type) {
+        (Some(t), Some(h)) if t == h => (t, DetectionConfidence::High),
+        // Conflicting signals: the header decides how the rows parse
+        (Some(_), Some(h)) => (h, DetectionConfidence::Medium),
+        (Some(t), None) | (None, Some(t)) => (t, DetectionConfidence::Medium),
+        (None, None) => (detect_file_type(filename), DetectionConfidence::Low),
+    };
+
+    // Prefer the account named in any section title over the filename
+    let titles: Vec<&str> = lines.iter().copied()
+        .filter(|l| l.starts_with(TRADING_TITLE) || l.starts_with(CASH_TITLE))
+        .collect();
+    let account_type = match titles.iter().find_map(|t| account_type_from_text(t)) {
+        Some(account) => account,
+        None => {
+            let account = extract_account_type(filename);
+            if account == AccountType::Other {
+                confidence = DetectionConfidence::Low;
+            } else if confidence > DetectionConfidence::Medium {
+                confidence = DetectionConfidence::Medium;
+            }
+            account
+        }
+    };
+
+    let portfolio = titles.iter()
+        .filter_map(|t| PORTFOLIO_RE.captures(t))
+        .map(|c| c[1].trim().to_string())
+        .find(|p| !p.eq_ignore_ascii_case("Cash"));
+
+    StatementInfo {
+        file_type,
+        account_type,
+        portfolio,
+        confidence,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseIssue {
+    pub file: String,
+    /// 1-based line number in the uploaded file
+    pub line: u64,
+    pub column: Option<String>,
+    pub raw_value: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} line {}", self.file, self.line)?;
+        if let Some(ref column) = self.column {
+            write!(f, ", column '{}'", column)?;
+        }
+        if let Some(ref raw) = self.raw_value {
+            write!(f, ", value '{}'", raw)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Strict rejects an upload if any row fails to parse; lenient stores the good rows
+/// and reports the bad ones.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseMode {
+    #[default]
+    Strict,
+    Lenient,
+}
+
+impl ParseMode {
+    /// Reads `PARSE_MODE` ("strict" or "lenient"), defaulting to strict.
+    pub fn from_env() -> Self {
+        match std::env::var("PARSE_MODE").map(|m| m.to_lowercase()).as_deref() {
+            Ok("lenient") => ParseMode::Lenient,
+            _ => ParseMode::Strict,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParsedRecords<T> {
+    pub records: Vec<T>,
+    pub issues: Vec<ParseIssue>,
+}
+
+impl<T> ParsedRecords<T> {
+    /// Fails with every collected issue if any row could not be parsed.
+    pub fn into_strict(self) -> Result<Vec<T>> {
+        if self.issues.is_empty() {
+            return Ok(self.records);
+        }
+        let details = self.issues.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("; ");
+        Err(anyhow!("{} row(s) failed to parse: {}", self.issues.len(), details))
+    }
+}
+
+/// Parses every row of `csv_content` with `parse_row`, collecting failures as `ParseIssue`s.
+/// `line_of` maps the csv reader's 1-based line (header = 1) to the line in the original file.
+fn parse_rows<T>(
+    filename: &str,
+    csv_content: String,
+    line_of: impl Fn(u64) -> u64,
+    parse_row: impl Fn(&BrokerRow) -> Result<T, ParseIssue>,
+) -> ParsedRecords<T> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(Cursor::new(csv_content));
+
+    let mut parsed = ParsedRecords { records: Vec::new(), issues: Vec::new() };
+
+    let headers = match rdr.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => {
+            parsed.issues.push(ParseIssue {
+                file: filename.to_string(),
+                line: line_of(1),
+                column: None,
+                raw_value: None,
+                reason: format!("Unreadable header row: {}", e),
+            });
+            return parsed;
+        }
+    };
+
+    for result in rdr.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                parsed.issues.push(ParseIssue {
+                    file: filename.to_string(),
+                    line: e.position().map(|p| line_of(p.line())).unwrap_or_default(),
+                    column: None,
+                    raw_value: None,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let row = BrokerRow {
+            file: filename,
+            line: record.position().map(|p| line_of(p.line())).unwrap_or_default(),
+            headers: &headers,
+            record: &record,
+        };
+        match parse_row(&row) {
+            Ok(record) => parsed.records.push(record),
+            Err(issue) => parsed.issues.push(issue),
+        }
+    }
+
+    parsed
+}
+
+/// Statement dates are `dd/mm/yy`, optionally with a time.
+fn statement_datetime(row: &BrokerRow, column: &str) -> Result<NaiveDateTime, ParseIssue> {
+    row.datetime(column, &["%d/%m/%y %H:%M:%S"]).or_else(|issue| {
+        NaiveDate::parse_from_str(row.get(column), "%d/%m/%y")
+            .map(|d| d.and_time(NaiveTime::MIN))
+            .map_err(|_| issue)
+    })
+}
+
+/// A transaction statement row; the caller sets the account type.
+fn trading_row(row: &BrokerRow) -> Result<TradingRecord, ParseIssue> {
+    let transaction_type = TransactionKind::try_from(row.get("Transaction Type").to_string())
+        .map_err(|reason| row.issue("Transaction Type", reason))?;
+    Ok(TradingRecord {
+        security_isin: row.get("Security / ISIN").to_string(),
+        transaction_type,
+        quantity: row.decimal("Quantity")?,
+        share_price: row.optional_decimal("Share Price")?.unwrap_or_default(),
+        total_trade_value: row.optional_decimal("Total Trade Value")?.unwrap_or_default(),
+        trade_date_time: statement_datetime(row, "Trade Date/Time")?,
+        settlement_date: statement_datetime(row, "Settlement Date")?,
+        broker: row.get("Broker").to_string(),
+        account_type: AccountType::default(),
+        ticker: None,
+    })
+}
+
+/// A cash statement row; the caller sets the account type, net flow and kind.
+fn cash_row(row: &BrokerRow) -> Result<CashRecord, ParseIssue> {
+    let date = NaiveDate::parse_from_str(row.get("Date"), "%d/%m/%y")
+        .map_err(|_| row.issue("Date", "Invalid date"))?;
+    Ok(CashRecord {
+        date,
+        activity: row.get("Activity").to_string(),
+        credit: row.optional_decimal("Credit")?,
+        debit: row.optional_decimal("Debit")?,
+        balance: row.optional_decimal("Balance")?,
+        account_type: AccountType::default(),
+        net_flow: Decimal::ZERO,
+        kind: CashActivityKind::default(),
+    })
+}
+
+pub fn parse_trading_files(file_data: Vec<(String, String)>) -> ParsedRecords<TradingRecord> {
     let mut all_records = Vec::new();
+    let mut all_issues = Vec::new();
 
     for (filename, content) in file_data {
-        let account_type = extract_account_type(&filename);
+        let account_type = detect_statement(&filename, &content).account_type;
         
         // Skip first line (title)
         let mut lines = content.lines();
         lines.next(); // skip "Transaction Statement: ..."
-        let remaining_content = lines.collect::<Vec<_>>().join("
-");
-
-        let mut rdr = csv::ReaderBuilder::new()
-            .has_headers(true)
-            .trim(csv::Trim::All)
-            .from_reader(Cursor::new(remaining_content));
+        let remaining_content = lines.collect::<Vec<_>>().join("\n");
 
-        for result in rdr.deserialize::<TradingRecord>() {
-            let mut record: TradingRecord = result.with_context(|| format!("Failed to deserialize trading record in {}", filename))?;
-            record.account_type = account_type.clone();
+        // +1 for the skipped title line
+        let parsed = parse_rows(&filename, remaining_content, |line| line + 1, trading_row);
+        for mut record in parsed.records {
+            record.account_type = account_type;
             all_records.push(record);
         }
+        all_issues.extend(parsed.issues);
     }
 
     // Sort by Trade Date/Time
     all_records.sort_by_key(|r| r.trade_date_time);
 
-    Ok(all_records)
+    ParsedRecords { records: all_records, issues: all_issues }
 }
 
-pub fn merge_cash_files(file_data: Vec<(String, String)>) -> Result<Vec<CashRecord>> {
+pub fn merge_trading_files(file_data: Vec<(String, String)>) -> Result<Vec<TradingRecord>> {
+    parse_trading_files(file_data).into_strict()
+}
+
+pub fn parse_cash_files(file_data: Vec<(String, String)>) -> ParsedRecords<CashRecord> {
     let mut all_records = Vec::new();
+    let mut all_issues = Vec::new();
 
     for (filename, content) in file_data {
-        let account_type = extract_account_type(&filename);
-        let mut current_df_lines = Vec::new();
+        let account_type = detect_statement(&filename, &content).account_type;
+        // (line number in the original file, line)
+        let mut current_df_lines: Vec<(u64, String)> = Vec::new();
         let mut headers = None;
         let mut skip_section = false;
 
-        for line in content.lines() {
+        for (idx, line) in content.lines().enumerate() {
+            let line_no = idx as u64 + 1;
             let line = line.trim();
             if line.is_empty() {
                 continue;
@@ -79,16 +385,13 @@ pub fn merge_cash_files(file_data: Vec<(String, String)>) -> Result<Vec<CashReco
             if line.starts_with("Cash Statement:") {
                 // Process previous section if any
                 if !current_df_lines.is_empty() && headers.is_some() {
-                    let section_records = parse_cash_section(headers.take().unwrap(), &current_df_lines, &account_type)?;
-                    all_records.extend(section_records);
+                    let section = parse_cash_section(&filename, headers.take().unwrap(), &current_df_lines, account_type);
+                    all_records.extend(section.records);
+                    all_issues.extend(section.issues);
                 }
                 current_df_lines.clear();
 
-                if line.contains("Portfolio: Cash") {
-                    skip_section = true;
-                } else {
-                    skip_section = false;
-                }
+                skip_section = line.contains("Portfolio: Cash");
                 continue;
             }
 
@@ -101,49 +404,150 @@ pub fn merge_cash_files(file_data: Vec<(String, String)>) -> Result<Vec<CashReco
                     headers = Some(line.to_string());
                 }
             } else {
-                current_df_lines.push(line.to_string());
+                current_df_lines.push((line_no, line.to_string()));
             }
         }
 
         // Process last section
-        if !current_df_lines.is_empty() && headers.is_some() {
-            let section_records = parse_cash_section(headers.unwrap(), &current_df_lines, &account_type)?;
-            all_records.extend(section_records);
+        if let Some(headers) = headers
+            && !current_df_lines.is_empty()
+        {
+            let section = parse_cash_section(&filename, headers, &current_df_lines, account_type);
+            all_records.extend(section.records);
+            all_issues.extend(section.issues);
         }
     }
 
     // Sort by Date
     all_records.sort_by_key(|r| r.date);
 
-    Ok(all_records)
+    ParsedRecords { records: all_records, issues: all_issues }
 }
 
-fn parse_cash_section(headers: String, lines: &[String], account_type: &str) -> Result<Vec<CashRecord>> {
-    let csv_content = format!("{}
-{}", headers, lines.join("
-"));
-    let mut rdr = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .trim(csv::Trim::All)
-        .from_reader(Cursor::new(csv_content));
+pub fn merge_cash_files(file_data: Vec<(String, String)>) -> Result<Vec<CashRecord>> {
+    parse_cash_files(file_data).into_strict()
+}
+
+fn parse_cash_section(filename: &str, headers: String, lines: &[(u64, String)], account_type: AccountType) -> ParsedRecords<CashRecord> {
+    let body = lines.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
+    let csv_content = format!("{}\n{}", headers, body);
+    // Blank lines were dropped above, so map the reader's line back through the kept lines
+    let parsed = parse_rows(filename, csv_content, |line| {
+        line.checked_sub(2)
+            .and_then(|i| lines.get(i as usize))
+            .map(|(n, _)| *n)
+            .unwrap_or(line)
+    }, cash_row);
 
     let mut records = Vec::new();
-    for result in rdr.deserialize::<CashRecord>() {
-        let mut record: CashRecord = result.context("Failed to deserialize cash record")?;
-        record.account_type = account_type.to_string();
+    for mut record in parsed.records {
+        record.account_type = account_type;
         
         // Calculate net_flow
         let credit = record.credit.unwrap_or_default();
         let debit = record.debit.unwrap_or_default();
         record.net_flow = credit - debit;
 
-        // Filter to external cash flow activities (similar to extract_cash_flows_only in Python)
-        let activity = record.activity.to_uppercase();
-        if activity.contains("PAYMENT RECEIVED") || 
-           activity.contains("WITHDRAWAL") || 
-           activity.contains("ISA TRANSFER IN") {
-            records.push(record);
-        }
+        record.kind = CashActivityKind::classify(&record.activity, record.net_flow);
+        records.push(record);
+    }
+    ParsedRecords { records, issues: parsed.issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TransactionKind;
+
+    const TRADING_CSV: &str = "Transaction Statement: ISA\n\
+Security / ISIN,Transaction Type,Quantity,Share Price,Total Trade Value,Trade Date/Time,Settlement Date,Broker\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Buy,2,£100.00,£200.00,05/06/22 10:00:00,07/06/22,Winterflood\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Buy,1,£1O1.00,£101.00,06/06/22 10:00:00,08/06/22,Winterflood\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Sell,1,£102.00,£102.00,2022-06-07,09/06/22,Winterflood\n";
+
+    #[test]
+    fn test_trading_issues_are_collected_per_row() {
+        let parsed = parse_trading_files(vec![("ISA_Trading.csv".to_string(), TRADING_CSV.to_string())]);
+        assert_eq!(parsed.records.len(), 1);
+        assert_eq!(parsed.issues.len(), 2);
+
+        let first = &parsed.issues[0];
+        assert_eq!(first.line, 4);
+        assert_eq!(first.column.as_deref(), Some("Share Price"));
+        assert_eq!(first.raw_value.as_deref(), Some("£1O1.00"));
+
+        let second = &parsed.issues[1];
+        assert_eq!(second.line, 5);
+        assert_eq!(second.column.as_deref(), Some("Trade Date/Time"));
+
+        let err = merge_trading_files(vec![("ISA_Trading.csv".to_string(), TRADING_CSV.to_string())]).unwrap_err();
+        assert!(err.to_string().contains("2 row(s) failed to parse"));
+    }
+
+    #[test]
+    fn test_unknown_transaction_type_is_reported() {
+        let content = "Transaction Statement: ISA\n\
+Security / ISIN,Transaction Type,Quantity,Share Price,Total Trade Value,Trade Date/Time,Settlement Date,Broker\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Dividend Reinvestment,1,£100.00,£100.00,05/06/22 10:00:00,07/06/22,Winterflood\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Gift,1,£100.00,£100.00,06/06/22 10:00:00,08/06/22,Winterflood\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Sell Reversal,1,£100.00,£100.00,07/06/22 10:00:00,09/06/22,Winterflood\n";
+        let parsed = parse_trading_files(vec![("ISA_Trading.csv".to_string(), content.to_string())]);
+        assert_eq!(parsed.records.len(), 1);
+        assert_eq!(parsed.records[0].transaction_type, TransactionKind::DividendReinvestment);
+        assert_eq!(parsed.issues.len(), 2);
+        assert_eq!(parsed.issues[0].column.as_deref(), Some("Transaction Type"));
+        assert_eq!(parsed.issues[0].raw_value.as_deref(), Some("Gift"));
+        // Types are matched exactly, not by the words they contain
+        assert_eq!(parsed.issues[1].raw_value.as_deref(), Some("Sell Reversal"));
+    }
+
+    #[test]
+    fn test_detect_statement_prefers_content_over_filename() {
+        let info = detect_statement("download (3).csv", TRADING_CSV);
+        assert_eq!(info.file_type, FileType::Trading);
+        assert_eq!(info.account_type, AccountType::ISA);
+        assert_eq!(info.confidence, DetectionConfidence::High);
+
+        let cash = "Cash Statement: General Investment Account, Portfolio: Cash\nDate,Activity,Credit,Debit,Balance\n\
+Cash Statement: General Investment Account, Portfolio: Global Equity\nDate,Activity,Credit,Debit,Balance\n";
+        let info = detect_statement("ISA_Trading_statement.csv", cash);
+        assert_eq!(info.file_type, FileType::Cash);
+        assert_eq!(info.account_type, AccountType::GIA);
+        assert_eq!(info.portfolio.as_deref(), Some("Global Equity"));
+
+        let info = detect_statement("GIA_CASH_statement.csv", "");
+        assert_eq!(info.file_type, FileType::Cash);
+        assert_eq!(info.confidence, DetectionConfidence::Low);
+    }
+
+    #[test]
+    fn test_account_types_from_titles_and_filenames() {
+        assert_eq!(detect_statement("x.csv", "Transaction Statement: Lifetime ISA\n").account_type, AccountType::LISA);
+        assert_eq!(detect_statement("x.csv", "Cash Statement: Junior ISA, Portfolio: Cash\n").account_type, AccountType::JISA);
+        assert_eq!(detect_statement("x.csv", "Transaction Statement: SIPP\n").account_type, AccountType::SIPP);
+        assert_eq!(extract_account_type("LISA_Trading_statement.csv"), AccountType::LISA);
+        assert_eq!(extract_account_type("ISA_Trading_statement.csv"), AccountType::ISA);
+        assert_eq!(extract_account_type("my-sipp-cash.csv"), AccountType::SIPP);
+        assert_eq!(extract_account_type("download.csv"), AccountType::Other);
+    }
+
+    #[test]
+    fn test_cash_issue_line_numbers_skip_blank_lines() {
+        let content = "Cash Statement: ISA\n\nDate,Activity,Credit,Debit,Balance\n05/06/22,Payment Received,£500.00,,£500.00\n\n06/06/22,Payment Received,£abc,,£600.00\n";
+        let parsed = parse_cash_files(vec![("ISA_Cash_.csv".to_string(), content.to_string())]);
+        assert_eq!(parsed.records.len(), 1);
+        assert_eq!(parsed.issues.len(), 1);
+        assert_eq!(parsed.issues[0].line, 6);
+        assert_eq!(parsed.issues[0].column.as_deref(), Some("Credit"));
+    }
+
+    #[test]
+    fn test_issue_column_is_the_failing_field() {
+        // The bad Debit value also appears, harmlessly, as the activity
+        let content = "Cash Statement: ISA\nDate,Activity,Credit,Debit,Balance\n06/06/22,n/a,,n/a,£600.00\n";
+        let parsed = parse_cash_files(vec![("ISA_Cash_.csv".to_string(), content.to_string())]);
+        assert_eq!(parsed.issues.len(), 1);
+        assert_eq!(parsed.issues[0].column.as_deref(), Some("Debit"));
+        assert_eq!(parsed.issues[0].raw_value.as_deref(), Some("n/a"));
     }
-    Ok(records)
 }
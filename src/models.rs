@@ -1,14 +1,169 @@
 use serde::{Deserialize, Deserializer, Serialize};
 use chrono::{NaiveDate, NaiveDateTime};
 use rust_decimal::Decimal;
+use std::fmt;
 use std::str::FromStr;
 
+/// Tax wrapper a statement row belongs to.
+///
+/// Stored as its upper-case name; anything unrecognised (including the legacy
+/// "Unknown") reads back as `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(from = "String")]
+pub enum AccountType {
+    GIA,
+    ISA,
+    LISA,
+    JISA,
+    SIPP,
+    #[default]
+    Other,
+}
+
+impl AccountType {
+    pub const ALL: [AccountType; 6] = [
+        AccountType::GIA,
+        AccountType::ISA,
+        AccountType::LISA,
+        AccountType::JISA,
+        AccountType::SIPP,
+        AccountType::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountType::GIA => "GIA",
+            AccountType::ISA => "ISA",
+            AccountType::LISA => "LISA",
+            AccountType::JISA => "JISA",
+            AccountType::SIPP => "SIPP",
+            AccountType::Other => "Other",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        Self::from_name(s).unwrap_or(AccountType::Other)
+    }
+
+    /// Strict counterpart of `parse`: none for names that are not an account type.
+    pub fn from_name(s: &str) -> Option<Self> {
+        let name = s.trim().to_uppercase();
+        AccountType::ALL.into_iter().find(|a| a.as_str().to_uppercase() == name)
+    }
+
+    /// Gains and income inside the wrapper are free of UK capital gains and income tax.
+    pub fn is_tax_sheltered(&self) -> bool {
+        matches!(self, AccountType::ISA | AccountType::LISA | AccountType::JISA | AccountType::SIPP)
+    }
+
+    /// Annual contribution limit in GBP (2024/25 rules). LISA subscriptions also count
+    /// towards the overall £20,000 ISA allowance; the SIPP figure is the pension annual allowance.
+    pub fn annual_allowance(&self) -> Option<Decimal> {
+        match self {
+            AccountType::ISA => Some(Decimal::from(20_000)),
+            AccountType::LISA => Some(Decimal::from(4_000)),
+            AccountType::JISA => Some(Decimal::from(9_000)),
+            AccountType::SIPP => Some(Decimal::from(60_000)),
+            AccountType::GIA | AccountType::Other => None,
+        }
+    }
+}
+
+impl From<String> for AccountType {
+    fn from(s: String) -> Self {
+        AccountType::parse(&s)
+    }
+}
+
+impl fmt::Display for AccountType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Trade direction, parsed from the statement's "Transaction Type" column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+    #[serde(rename = "Dividend Reinvestment")]
+    DividendReinvestment,
+    /// Shares moved in from another provider (in-specie transfer)
+    #[serde(rename = "Transfer In")]
+    TransferIn,
+    #[serde(rename = "Transfer Out")]
+    TransferOut,
+    /// A type the statements use that we do not know; it changes neither holdings nor cash
+    Other,
+}
+
+impl TransactionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionKind::Buy => "Buy",
+            TransactionKind::Sell => "Sell",
+            TransactionKind::DividendReinvestment => "Dividend Reinvestment",
+            TransactionKind::TransferIn => "Transfer In",
+            TransactionKind::TransferOut => "Transfer Out",
+            TransactionKind::Other => "Other",
+        }
+    }
+
+    /// Matches the statement's value exactly, ignoring case and surrounding whitespace.
+    pub fn parse(s: &str) -> Self {
+        match s.trim().to_uppercase().as_str() {
+            "BUY" => TransactionKind::Buy,
+            "SELL" => TransactionKind::Sell,
+            "DIVIDEND REINVESTMENT" => TransactionKind::DividendReinvestment,
+            "TRANSFER IN" => TransactionKind::TransferIn,
+            "TRANSFER OUT" => TransactionKind::TransferOut,
+            _ => TransactionKind::Other,
+        }
+    }
+
+    /// Signed change in units held.
+    pub fn holding_change(&self, quantity: Decimal) -> Decimal {
+        match self {
+            TransactionKind::Buy | TransactionKind::DividendReinvestment | TransactionKind::TransferIn => quantity,
+            TransactionKind::Sell | TransactionKind::TransferOut => -quantity,
+            TransactionKind::Other => Decimal::ZERO,
+        }
+    }
+
+    /// Signed new money put into the holding. Reinvested dividends are growth, not contributions.
+    pub fn contribution(&self, value: Decimal) -> Decimal {
+        match self {
+            TransactionKind::Buy | TransactionKind::TransferIn => value,
+            TransactionKind::Sell | TransactionKind::TransferOut => -value,
+            TransactionKind::DividendReinvestment | TransactionKind::Other => Decimal::ZERO,
+        }
+    }
+}
+
+impl TryFrom<String> for TransactionKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match TransactionKind::parse(&s) {
+            TransactionKind::Other => Err(format!("Unknown transaction type '{}'", s)),
+            kind => Ok(kind),
+        }
+    }
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TradingRecord {
     #[serde(rename = "Security / ISIN")]
     pub security_isin: String,
     #[serde(rename = "Transaction Type")]
-    pub transaction_type: String,
+    pub transaction_type: TransactionKind,
     #[serde(rename = "Quantity")]
     #[serde(deserialize_with = "deserialize_decimal")]
     pub quantity: Decimal,
@@ -27,11 +182,94 @@ pub struct TradingRecord {
     #[serde(rename = "Broker")]
     pub broker: String,
     #[serde(default, rename = "Account_Type")]
-    pub account_type: String,
+    pub account_type: AccountType,
     #[serde(skip_deserializing)]
     pub ticker: Option<String>,
 }
 
+/// What a cash statement row represents, classified from its activity text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CashActivityKind {
+    Deposit,
+    Withdrawal,
+    Dividend,
+    Fee,
+    Interest,
+    BuySettlement,
+    SellSettlement,
+    Transfer,
+    #[default]
+    Other,
+}
+
+impl CashActivityKind {
+    pub fn classify(activity: &str, net_flow: Decimal) -> Self {
+        let activity = activity.to_uppercase();
+        let has = |words: &[&str]| words.iter().any(|w| activity.contains(w));
+
+        if has(&["TRANSFER"]) {
+            CashActivityKind::Transfer
+        } else if has(&["PAYMENT RECEIVED", "DEPOSIT", "TOP UP", "TOP-UP"]) {
+            CashActivityKind::Deposit
+        } else if has(&["WITHDRAWAL"]) {
+            CashActivityKind::Withdrawal
+        } else if has(&["DIVIDEND", "DISTRIBUTION"]) {
+            CashActivityKind::Dividend
+        } else if has(&["INTEREST"]) {
+            CashActivityKind::Interest
+        } else if has(&["FEE", "CHARGE", "COMMISSION"]) {
+            CashActivityKind::Fee
+        } else if has(&["BUY", "PURCHASE"]) {
+            CashActivityKind::BuySettlement
+        } else if has(&["SELL", "SALE"]) {
+            CashActivityKind::SellSettlement
+        } else if has(&["SETTLEMENT", "TRADE"]) {
+            if net_flow < Decimal::ZERO { CashActivityKind::BuySettlement } else { CashActivityKind::SellSettlement }
+        } else {
+            CashActivityKind::Other
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CashActivityKind::Deposit => "deposit",
+            CashActivityKind::Withdrawal => "withdrawal",
+            CashActivityKind::Dividend => "dividend",
+            CashActivityKind::Fee => "fee",
+            CashActivityKind::Interest => "interest",
+            CashActivityKind::BuySettlement => "buy_settlement",
+            CashActivityKind::SellSettlement => "sell_settlement",
+            CashActivityKind::Transfer => "transfer",
+            CashActivityKind::Other => "other",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "deposit" => Some(CashActivityKind::Deposit),
+            "withdrawal" => Some(CashActivityKind::Withdrawal),
+            "dividend" => Some(CashActivityKind::Dividend),
+            "fee" => Some(CashActivityKind::Fee),
+            "interest" => Some(CashActivityKind::Interest),
+            "buy_settlement" => Some(CashActivityKind::BuySettlement),
+            "sell_settlement" => Some(CashActivityKind::SellSettlement),
+            "transfer" => Some(CashActivityKind::Transfer),
+            "other" => Some(CashActivityKind::Other),
+            _ => None,
+        }
+    }
+
+    /// Money crossing the account boundary: contributions, withdrawals and transfers from other providers.
+    pub fn is_external(&self) -> bool {
+        matches!(self, CashActivityKind::Deposit | CashActivityKind::Withdrawal | CashActivityKind::Transfer)
+    }
+
+    pub fn is_income(&self) -> bool {
+        matches!(self, CashActivityKind::Dividend | CashActivityKind::Interest)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CashRecord {
     #[serde(rename = "Date")]
@@ -45,13 +283,17 @@ pub struct CashRecord {
     #[serde(rename = "Debit")]
     #[serde(deserialize_with = "deserialize_optional_currency")]
     pub debit: Option<Decimal>,
+    /// The statement's own running balance; none for brokers whose exports have no balance
+    /// column, where any balance would be made up per file
     #[serde(rename = "Balance")]
-    #[serde(deserialize_with = "deserialize_currency")]
-    pub balance: Decimal,
+    #[serde(deserialize_with = "deserialize_optional_currency")]
+    pub balance: Option<Decimal>,
     #[serde(default, rename = "Account_Type")]
-    pub account_type: String,
+    pub account_type: AccountType,
     #[serde(default)]
     pub net_flow: Decimal,
+    #[serde(default, skip_deserializing)]
+    pub kind: CashActivityKind,
 }
 
 fn deserialize_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
@@ -59,8 +301,8 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    let s = s.replace(",", "");
-    Decimal::from_str(&s).map_err(serde::de::Error::custom)
+    let clean = s.replace(",", "");
+    Decimal::from_str(clean.trim()).map_err(|_| serde::de::Error::custom(format!("Invalid number '{}'", s)))
 }
 
 fn deserialize_currency<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
@@ -72,7 +314,7 @@ where
     if clean.is_empty() {
         return Ok(Decimal::ZERO);
     }
-    Decimal::from_str(&clean).map_err(serde::de::Error::custom)
+    Decimal::from_str(&clean).map_err(|_| serde::de::Error::custom(format!("Invalid currency amount '{}'", s)))
 }
 
 fn deserialize_optional_currency<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
@@ -86,7 +328,7 @@ where
             if clean.is_empty() {
                 Ok(None)
             } else {
-                Ok(Some(Decimal::from_str(&clean).map_err(serde::de::Error::custom)?))
+                Ok(Some(Decimal::from_str(&clean).map_err(|_| serde::de::Error::custom(format!("Invalid currency amount '{}'", s)))?))
             }
         }
         None => Ok(None),
@@ -106,7 +348,7 @@ where
     if let Ok(d) = NaiveDate::parse_from_str(&s, "%d/%m/%y") {
         return Ok(d.and_hms_opt(0, 0, 0).unwrap());
     }
-    Err(serde::de::Error::custom(format!("Invalid datetime format: {}", s)))
+    Err(serde::de::Error::custom(format!("Invalid datetime '{}'", s)))
 }
 
 fn deserialize_date_to_datetime<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
@@ -120,7 +362,7 @@ where
     if let Ok(dt) = NaiveDateTime::parse_from_str(&s, "%d/%m/%y %H:%M:%S") {
         return Ok(dt);
     }
-    Err(serde::de::Error::custom(format!("Invalid date format: {}", s)))
+    Err(serde::de::Error::custom(format!("Invalid date '{}'", s)))
 }
 
 fn deserialize_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
@@ -128,5 +370,5 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    NaiveDate::parse_from_str(&s, "%d/%m/%y").map_err(serde::de::Error::custom)
+    NaiveDate::parse_from_str(&s, "%d/%m/%y").map_err(|_| serde::de::Error::custom(format!("Invalid date '{}'", s)))
 }
@@ -0,0 +1,229 @@
+use crate::portfolio_stats::calculate_xirr;
+use chrono::{Datelike, Months, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// Periods shorter than this are reported cumulatively only
+const MIN_ANNUALISED_DAYS: i64 = 365;
+
+/// Trailing windows, in months back from the last date
+const TRAILING_WINDOWS: [(&str, u32); 5] = [("1M", 1), ("3M", 3), ("1Y", 12), ("3Y", 36), ("5Y", 60)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeriodKind {
+    Trailing,
+    YearToDate,
+    SinceInception,
+    CalendarYear,
+    /// UK tax year, 6 April to 5 April
+    TaxYear,
+}
+
+/// Time- and money-weighted returns over one period.
+///
+/// `start` is the valuation the period grows from, i.e. the close of the day before its
+/// first day, so the calendar year 2024 runs from 2023-12-31 to 2024-12-31.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodReturn {
+    pub label: String,
+    pub kind: PeriodKind,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// False when the history starts after the period does or the period has not ended
+    pub complete: bool,
+    pub start_value: Decimal,
+    pub end_value: Decimal,
+    /// External flows within the period; deposits positive
+    pub net_flows: Decimal,
+    /// Cumulative
+    pub twr: f64,
+    pub twr_annualised: Option<f64>,
+    /// Cumulative; none without money in or out of the period
+    pub mwr: Option<f64>,
+    pub mwr_annualised: Option<f64>,
+}
+
+/// Daily returns with external flows taken out: `(value - flow) / previous value - 1`.
+///
+/// `flows` are aligned with `values`. The first day, and any day after a zero value, has a
+/// return of zero.
+pub fn flow_adjusted_returns(values: &[Decimal], flows: &[Decimal]) -> Vec<f64> {
+    let mut returns = vec![0.0; values.len()];
+    for i in 1..values.len() {
+        let previous = values[i - 1];
+        if previous <= Decimal::ZERO {
+            continue;
+        }
+        let flow = flows.get(i).copied().unwrap_or_default();
+        returns[i] = ((values[i] - flow) / previous).to_f64().unwrap_or(1.0) - 1.0;
+    }
+    returns
+}
+
+/// Standard trailing windows, year to date, since inception and every calendar and UK tax
+/// year of the history.
+///
+/// `dates` are consecutive days with the portfolio's `values` and external `flows` on each.
+/// Trailing windows longer than the history are left out; years the history only partly
+/// covers are included but marked incomplete.
+pub fn calculate_period_returns(dates: &[NaiveDate], values: &[Decimal], flows: &[Decimal]) -> Vec<PeriodReturn> {
+    let (Some(&inception), Some(&end)) = (dates.first(), dates.last()) else {
+        return Vec::new();
+    };
+    if dates.len() != values.len() || dates.len() != flows.len() {
+        return Vec::new();
+    }
+    let returns = flow_adjusted_returns(values, flows);
+    let period = |label: String, kind: PeriodKind, start: NaiveDate, period_end: NaiveDate| {
+        let complete = start >= inception && period_end <= end;
+        let mut result = measure(dates, values, flows, &returns, start.max(inception), period_end.min(end));
+        result.label = label;
+        result.kind = kind;
+        result.complete = complete;
+        result
+    };
+
+    let mut periods = Vec::new();
+    let year_start = NaiveDate::from_ymd_opt(end.year() - 1, 12, 31).unwrap_or(inception);
+    periods.push(period("YTD".to_string(), PeriodKind::YearToDate, year_start, end));
+    for (label, months) in TRAILING_WINDOWS {
+        if let Some(start) = end.checked_sub_months(Months::new(months))
+            && start >= inception
+        {
+            periods.push(period(label.to_string(), PeriodKind::Trailing, start, end));
+        }
+    }
+    periods.push(period("Since inception".to_string(), PeriodKind::SinceInception, inception, end));
+
+    for year in inception.year()..=end.year() {
+        let (Some(start), Some(year_end)) = (
+            NaiveDate::from_ymd_opt(year - 1, 12, 31),
+            NaiveDate::from_ymd_opt(year, 12, 31),
+        ) else {
+            continue;
+        };
+        periods.push(period(year.to_string(), PeriodKind::CalendarYear, start, year_end));
+    }
+
+    let first_tax_year = tax_year_of(inception);
+    for year in first_tax_year..=tax_year_of(end) {
+        let (Some(start), Some(year_end)) = (
+            NaiveDate::from_ymd_opt(year, 4, 5),
+            NaiveDate::from_ymd_opt(year + 1, 4, 5),
+        ) else {
+            continue;
+        };
+        let label = format!("{}/{:02}", year, (year + 1) % 100);
+        periods.push(period(label, PeriodKind::TaxYear, start, year_end));
+    }
+
+    periods
+}
+
+/// The calendar year a UK tax year starts in, e.g. 2024 for 2024/25.
+pub fn tax_year_of(date: NaiveDate) -> i32 {
+    if (date.month(), date.day()) >= (4, 6) { date.year() } else { date.year() - 1 }
+}
+
+/// Returns between the closes of `start` and `end`, both within `dates`.
+fn measure(
+    dates: &[NaiveDate],
+    values: &[Decimal],
+    flows: &[Decimal],
+    returns: &[f64],
+    start: NaiveDate,
+    end: NaiveDate,
+) -> PeriodReturn {
+    let first = dates[0];
+    let index = |date: NaiveDate| ((date - first).num_days().max(0) as usize).min(dates.len() - 1);
+    let (from, to) = (index(start), index(end));
+    let days = (end - start).num_days();
+
+    let twr = returns[from + 1..=to].iter().fold(1.0, |acc, r| acc * (1.0 + r)) - 1.0;
+    let net_flows: Decimal = flows[from + 1..=to].iter().sum();
+
+    // The opening value is invested at the start and the closing value taken out at the end
+    let mut xirr_dates = vec![dates[from]];
+    let mut xirr_amounts = vec![-values[from].to_f64().unwrap_or(0.0)];
+    for i in from + 1..=to {
+        if !flows[i].is_zero() {
+            xirr_dates.push(dates[i]);
+            xirr_amounts.push(-flows[i].to_f64().unwrap_or(0.0));
+        }
+    }
+    xirr_dates.push(dates[to]);
+    xirr_amounts.push(values[to].to_f64().unwrap_or(0.0));
+    let has_money = xirr_amounts.iter().any(|a| *a < 0.0) && xirr_amounts.iter().any(|a| *a > 0.0);
+    let mwr_annualised = (has_money && days > 0).then(|| calculate_xirr(&xirr_dates, &xirr_amounts, 0.1));
+    let mwr = mwr_annualised.map(|r| (1.0 + r).powf(days as f64 / 365.25) - 1.0);
+
+    let annualised = days >= MIN_ANNUALISED_DAYS;
+    PeriodReturn {
+        label: String::new(),
+        kind: PeriodKind::Trailing,
+        start,
+        end,
+        complete: true,
+        start_value: values[from],
+        end_value: values[to],
+        net_flows,
+        twr,
+        twr_annualised: (annualised && twr > -1.0).then(|| (1.0 + twr).powf(365.25 / days as f64) - 1.0),
+        mwr,
+        mwr_annualised: mwr_annualised.filter(|_| annualised),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_period_returns() {
+        // 1000 deposited on 2023-03-01 grows 10% by the end of June; 500 more goes in on
+        // 2023-07-01 and the lot grows another 10% by 2024-06-30
+        let dates: Vec<NaiveDate> = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap()
+            .iter_days()
+            .take_while(|d| *d <= NaiveDate::from_ymd_opt(2024, 6, 30).unwrap())
+            .collect();
+        let deposit = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap();
+        let mut values = Vec::new();
+        let mut flows = Vec::new();
+        for &date in &dates {
+            let value = if date < deposit {
+                let progress = Decimal::from((date - dates[0]).num_days()) / dec!(121);
+                dec!(1000) + dec!(100) * progress
+            } else {
+                let progress = Decimal::from((date - deposit).num_days()) / dec!(365);
+                dec!(1600) + dec!(160) * progress
+            };
+            values.push(value);
+            flows.push(if date == dates[0] { dec!(1000) } else if date == deposit { dec!(500) } else { dec!(0) });
+        }
+        let periods = calculate_period_returns(&dates, &values, &flows);
+        let find = |label: &str| periods.iter().find(|p| p.label == label).unwrap();
+
+        let inception = find("Since inception");
+        assert!((inception.twr - 0.21).abs() < 1e-9, "twr {}", inception.twr);
+        assert_eq!(inception.net_flows, dec!(500));
+        assert!(inception.twr_annualised.is_some());
+        assert!(inception.mwr.unwrap() > 0.15 && inception.mwr.unwrap() < 0.21);
+
+        let ytd = find("YTD");
+        assert!(ytd.complete);
+        assert_eq!(ytd.start, NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+        assert!(ytd.twr_annualised.is_none());
+
+        assert!(periods.iter().all(|p| p.label != "3Y"));
+        assert!(!find("2023").complete);
+        assert!(!find("2024").complete);
+        let tax_year = find("2023/24");
+        assert!(tax_year.complete);
+        assert_eq!(tax_year.start, NaiveDate::from_ymd_opt(2023, 4, 5).unwrap());
+        assert_eq!(find("2022/23").start, dates[0]);
+        assert_eq!(tax_year_of(NaiveDate::from_ymd_opt(2024, 4, 5).unwrap()), 2023);
+    }
+}
@@ -12,6 +12,8 @@ pub struct PortfolioStats {
     pub profit_loss: Decimal,
     pub return_percentage: Decimal,
     pub calc_date: NaiveDate,
+    /// Currency every amount above is expressed in
+    pub currency: String,
 }
 
 pub fn calculate_xirr(dates: &[NaiveDate], amounts: &[f64], guess: f64) -> f64 {
@@ -145,6 +147,7 @@ pub fn calculate_portfolio_stats(
     current_value: Decimal,
     current_date: NaiveDate,
     daily_portfolio_values: Option<(&[NaiveDate], &[Decimal])>,
+    currency: &str,
 ) -> PortfolioStats {
     let mut total_invested = Decimal::ZERO;
     let mut total_withdrawn = Decimal::ZERO;
@@ -190,5 +193,104 @@ pub fn calculate_portfolio_stats(
         profit_loss,
         return_percentage,
         calc_date: current_date,
+        currency: currency.to_string(),
+    }
+}
+
+/// Returns on one security, in the portfolio's currency.
+pub struct HoldingStats {
+    pub ticker: String,
+    /// Money-weighted, annualised
+    pub irr: f64,
+    /// Time-weighted, annualised
+    pub twr: f64,
+    /// Paid for buys and transfers in, plus reinvested dividends when income is paid out
+    pub invested: Decimal,
+    /// Received from sales and transfers out
+    pub proceeds: Decimal,
+    pub income: Decimal,
+    pub current_value: Decimal,
+    /// Value plus proceeds plus income, less invested
+    pub gain: Decimal,
+    pub return_percentage: Decimal,
+    pub currency: String,
+}
+
+/// Per-security version of `calculate_portfolio_stats`.
+///
+/// `flows` are money into the holding (buys positive, sales negative) and `distributions`
+/// income paid out of it. `daily_values` is the holding's value on each of `daily_dates`.
+pub fn calculate_holding_stats(
+    ticker: &str,
+    flows: &[(NaiveDate, Decimal)],
+    distributions: &[(NaiveDate, Decimal)],
+    daily_dates: &[NaiveDate],
+    daily_values: &[Decimal],
+    currency: &str,
+) -> HoldingStats {
+    let current_date = daily_dates.last().copied().unwrap_or_default();
+    let current_value = daily_values.last().copied().unwrap_or_default();
+
+    let invested: Decimal = flows.iter().map(|(_, f)| *f).filter(|f| *f > Decimal::ZERO).sum();
+    let proceeds: Decimal = flows.iter().map(|(_, f)| -*f).filter(|f| *f > Decimal::ZERO).sum();
+    let income: Decimal = distributions.iter().map(|(_, d)| *d).sum();
+
+    // From the investor's side: buys are outflows, sales, income and the final value inflows
+    let mut xirr_dates = Vec::new();
+    let mut xirr_amounts = Vec::new();
+    for (date, flow) in flows {
+        xirr_dates.push(*date);
+        xirr_amounts.push(-flow.to_f64().unwrap_or(0.0));
+    }
+    for (date, amount) in distributions {
+        xirr_dates.push(*date);
+        xirr_amounts.push(amount.to_f64().unwrap_or(0.0));
+    }
+    xirr_dates.push(current_date);
+    xirr_amounts.push(current_value.to_f64().unwrap_or(0.0));
+    let irr = calculate_xirr(&xirr_dates, &xirr_amounts, 0.1);
+
+    let twr_events: Vec<(NaiveDate, Decimal)> = flows.iter().copied()
+        .chain(distributions.iter().map(|(d, a)| (*d, -*a)))
+        .collect();
+    let twr = calculate_twr(daily_dates, daily_values, &twr_events, current_date);
+
+    let gain = current_value + proceeds + income - invested;
+    let return_percentage = if invested.is_zero() { Decimal::ZERO } else { gain / invested };
+
+    HoldingStats {
+        ticker: ticker.to_string(),
+        irr,
+        twr,
+        invested,
+        proceeds,
+        income,
+        current_value,
+        gain,
+        return_percentage,
+        currency: currency.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_holding_stats() {
+        let start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let dates: Vec<NaiveDate> = start.iter_days().take(366).collect();
+        // Bought for 1000, grows steadily to 1100 over the year and pays out 20
+        let values: Vec<Decimal> = (0..366).map(|i| dec!(1000) + Decimal::from(i) * dec!(100) / dec!(365)).collect();
+        let flows = vec![(start, dec!(1000))];
+        let distributions = vec![(dates[180], dec!(20))];
+
+        let stats = calculate_holding_stats("VWRP.L", &flows, &distributions, &dates, &values, "GBP");
+        assert_eq!(stats.invested, dec!(1000));
+        assert_eq!(stats.income, dec!(20));
+        assert_eq!(stats.gain, dec!(120));
+        assert!((stats.irr - 0.12).abs() < 0.005, "irr {}", stats.irr);
+        assert!(stats.twr > 0.1 && stats.twr < 0.13, "twr {}", stats.twr);
     }
 }
@@ -0,0 +1,152 @@
+use crate::merge_csv::{DetectionConfidence, FileType, ParseIssue};
+use crate::models::AccountType;
+use crate::security_parser::extract_security_and_isin;
+use crate::statement_parser::ParserRegistry;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+pub struct FilePreview {
+    pub filename: String,
+    pub broker: &'static str,
+    pub file_type: FileType,
+    pub account_type: AccountType,
+    pub portfolio: Option<String>,
+    pub confidence: DetectionConfidence,
+    pub records: usize,
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+    pub issues: Vec<ParseIssue>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UploadPreview {
+    pub files: Vec<FilePreview>,
+    pub total_trading_transactions: usize,
+    pub total_cash_flows: usize,
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+    /// ISIN -> security name for every security seen in the trading files
+    pub securities: BTreeMap<String, String>,
+}
+
+/// Parses uploaded statements exactly like `/upload/` would, without touching the database.
+pub fn preview_files(registry: &ParserRegistry, files: Vec<(String, String)>) -> UploadPreview {
+    let mut preview = UploadPreview::default();
+
+    for (filename, content) in files {
+        let Some((broker, info, parsed)) = registry.parse(&filename, &content) else {
+            continue;
+        };
+
+        let dates = parsed.trades.iter().map(|r| r.trade_date_time.date())
+            .chain(parsed.cash.iter().map(|r| r.date));
+        let file_preview = FilePreview {
+            filename,
+            broker,
+            file_type: info.file_type,
+            account_type: info.account_type,
+            portfolio: info.portfolio,
+            confidence: info.confidence,
+            records: parsed.trades.len() + parsed.cash.len(),
+            date_from: dates.clone().min(),
+            date_to: dates.max(),
+            issues: parsed.issues,
+        };
+
+        preview.total_trading_transactions += parsed.trades.len();
+        preview.total_cash_flows += parsed.cash.len();
+        for record in &parsed.trades {
+            let (name, isin_opt) = extract_security_and_isin(&record.security_isin);
+            if let Some(isin) = isin_opt {
+                preview.securities.entry(isin).or_insert(name);
+            }
+        }
+
+        preview.date_from = match (preview.date_from, file_preview.date_from) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        preview.date_to = match (preview.date_to, file_preview.date_to) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        preview.files.push(file_preview);
+    }
+
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRADING_CSV: &str = "Transaction Statement: Stocks & Shares ISA\n\
+Security / ISIN,Transaction Type,Quantity,Share Price,Total Trade Value,Trade Date/Time,Settlement Date,Broker\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Buy,2,£100.00,£200.00,05/06/22 10:00:00,07/06/22,Winterflood\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Buy,1,£1O1.00,£101.00,06/06/22 10:00:00,08/06/22,Winterflood\n\
+iShares Core MSCI EM / ISIN IE00BKM4GZ66,Buy,3,£30.00,£90.00,09/06/22 10:00:00,13/06/22,Winterflood\n";
+
+    const CASH_CSV: &str = "Cash Statement: General Investment Account, Portfolio: Global Equity\n\
+Date,Activity,Credit,Debit,Balance\n\
+01/06/22,Payment Received,£500.00,,£500.00\n\
+20/06/22,Management Fee,,£0.50,£499.50\n";
+
+    fn files() -> Vec<(String, String)> {
+        vec![
+            ("download.csv".to_string(), TRADING_CSV.to_string()),
+            ("statement (2).csv".to_string(), CASH_CSV.to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_preview_detects_each_file() {
+        // Neither filename says anything, so detection comes from the content
+        let preview = preview_files(&ParserRegistry::default(), files());
+        assert_eq!(preview.files.len(), 2);
+
+        let trading = &preview.files[0];
+        assert_eq!(trading.broker, "InvestEngine");
+        assert_eq!(trading.file_type, FileType::Trading);
+        assert_eq!(trading.account_type, AccountType::ISA);
+        assert_eq!(trading.confidence, DetectionConfidence::High);
+
+        let cash = &preview.files[1];
+        assert_eq!(cash.file_type, FileType::Cash);
+        assert_eq!(cash.account_type, AccountType::GIA);
+        assert_eq!(cash.portfolio.as_deref(), Some("Global Equity"));
+        assert_eq!(cash.records, 2);
+        assert_eq!(cash.date_to, NaiveDate::from_ymd_opt(2022, 6, 20));
+    }
+
+    #[test]
+    fn test_preview_reports_issues_with_totals() {
+        let preview = preview_files(&ParserRegistry::default(), files());
+        let trading = &preview.files[0];
+        assert_eq!(trading.records, 2);
+        assert_eq!(trading.issues.len(), 1);
+        assert_eq!(trading.issues[0].line, 4);
+        assert_eq!(trading.issues[0].column.as_deref(), Some("Share Price"));
+
+        assert_eq!(preview.total_trading_transactions, 2);
+        assert_eq!(preview.total_cash_flows, 2);
+        assert_eq!(preview.date_from, NaiveDate::from_ymd_opt(2022, 6, 1));
+        assert_eq!(preview.date_to, NaiveDate::from_ymd_opt(2022, 6, 20));
+        let isins: Vec<&str> = preview.securities.keys().map(String::as_str).collect();
+        assert_eq!(isins, vec!["IE00BK5BQT80", "IE00BKM4GZ66"]);
+    }
+
+    #[test]
+    fn test_preview_keeps_no_state() {
+        // Nothing is stored between previews, so the same files never count as duplicates
+        let registry = ParserRegistry::default();
+        let first = preview_files(&registry, files());
+        let second = preview_files(&registry, files());
+        assert_eq!(
+            serde_json::to_value(&first).unwrap(),
+            serde_json::to_value(&second).unwrap(),
+        );
+        assert_eq!(second.total_trading_transactions, 2);
+    }
+}
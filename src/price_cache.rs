@@ -0,0 +1,111 @@
+use crate::database::Database;
+use crate::price_source::PriceSource;
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use tracing::{info, warn};
+
+/// Date range already stored in the `prices` collection for one ticker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceCoverage {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Day of the last fetch; that day's bar may still have been moving
+    pub fetched_on: NaiveDate,
+}
+
+impl PriceCoverage {
+    /// Last date whose bar is final.
+    fn settled_through(&self) -> NaiveDate {
+        self.to.min(self.fetched_on - Duration::days(1))
+    }
+
+    fn extend(self, start: NaiveDate, end: NaiveDate, today: NaiveDate) -> Self {
+        PriceCoverage { from: self.from.min(start), to: self.to.max(end), fetched_on: today }
+    }
+}
+
+/// Range that still has to be fetched to serve `start..=end`, if any.
+///
+/// Only an earlier start or bars after the last settled one trigger a fetch; the fetch
+/// covers everything from the first missing date to `end`.
+pub fn missing_range(coverage: Option<PriceCoverage>, start: NaiveDate, end: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let Some(coverage) = coverage else {
+        return Some((start, end));
+    };
+    if start < coverage.from {
+        return Some((start, end));
+    }
+    let settled = coverage.settled_through();
+    (end > settled).then(|| (settled + Duration::days(1), end))
+}
+
+/// Bars served from the cache, with the provider error if the missing range could not be fetched.
+#[derive(Debug, Clone, Default)]
+pub struct CachedPrices {
+    pub bars: Vec<(NaiveDate, Decimal, String)>,
+    pub fetch_error: Option<String>,
+}
+
+/// Daily closes for `ticker` from the cache, fetching only what is missing.
+///
+/// If the provider fails, whatever is cached is returned with the error attached so a
+/// flaky upstream does not block a precompute run; the call itself only fails when
+/// nothing is cached at all.
+pub async fn get_cached_prices(
+    db: &Database,
+    source: &dyn PriceSource,
+    ticker: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<CachedPrices> {
+    let coverage = db.get_price_coverage(ticker).await?;
+    let mut fetch_error = None;
+
+    if let Some((fetch_from, fetch_to)) = missing_range(coverage, start, end) {
+        match source.historical_prices(ticker, fetch_from, fetch_to).await {
+            Ok(bars) => {
+                info!("Cached {} new bars for {} ({} to {})", bars.len(), ticker, fetch_from, fetch_to);
+                db.save_prices(ticker, fetch_from, fetch_to, &bars).await?;
+                let today = Utc::now().date_naive();
+                let updated = match coverage {
+                    Some(c) => c.extend(start, end, today),
+                    None => PriceCoverage { from: start, to: end, fetched_on: today },
+                };
+                db.save_price_coverage(ticker, &updated).await?;
+            }
+            Err(e) if coverage.is_some() => {
+                warn!("Price fetch for {} failed, using cached bars: {}", ticker, e);
+                fetch_error = Some(e.to_string());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let bars = db.load_prices(ticker, start, end).await?;
+    Ok(CachedPrices { bars, fetch_error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, day).unwrap()
+    }
+
+    #[test]
+    fn test_missing_range_is_incremental() {
+        assert_eq!(missing_range(None, d(2022, 1, 1), d(2024, 6, 1)), Some((d(2022, 1, 1), d(2024, 6, 1))));
+
+        let coverage = PriceCoverage { from: d(2022, 1, 1), to: d(2024, 6, 1), fetched_on: d(2024, 6, 1) };
+        // The bar for the day of the last fetch is fetched again
+        assert_eq!(missing_range(Some(coverage), d(2022, 1, 1), d(2024, 6, 10)), Some((d(2024, 6, 1), d(2024, 6, 10))));
+        assert_eq!(missing_range(Some(coverage), d(2023, 1, 1), d(2024, 5, 31)), None);
+        // An earlier start refetches from the new start
+        assert_eq!(missing_range(Some(coverage), d(2021, 1, 1), d(2024, 5, 1)), Some((d(2021, 1, 1), d(2024, 5, 1))));
+
+        let fetched_later = PriceCoverage { fetched_on: d(2024, 7, 1), ..coverage };
+        assert_eq!(missing_range(Some(fetched_later), d(2022, 1, 1), d(2024, 6, 1)), None);
+    }
+}
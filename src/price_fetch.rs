@@ -0,0 +1,240 @@
+use crate::database::Database;
+use crate::env::parse_var;
+use crate::price_cache::get_cached_prices;
+use crate::price_source::{PriceSource, SymbolMatch};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use futures::stream::{self, StreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Spaces out calls so that no more than a fixed number start per second.
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// A limit of zero or less means unlimited.
+    pub fn per_second(rate: f64) -> Self {
+        let interval = (rate > 0.0).then(|| Duration::from_secs_f64(1.0 / rate));
+        Self { interval, next_slot: Mutex::new(Instant::now()) }
+    }
+
+    /// Waits for the next free slot.
+    pub async fn acquire(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let slot = {
+            let mut next = self.next_slot.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Applies a provider's rate limit to every call made through it.
+pub struct RateLimitedSource {
+    inner: Arc<dyn PriceSource>,
+    limiter: RateLimiter,
+}
+
+impl RateLimitedSource {
+    pub fn new(inner: Arc<dyn PriceSource>, requests_per_second: f64) -> Self {
+        Self { inner, limiter: RateLimiter::per_second(requests_per_second) }
+    }
+
+    /// Reads `PRICE_RATE_LIMIT_<PROVIDER>` in requests per second, e.g. `PRICE_RATE_LIMIT_YAHOO=2`.
+    /// Yahoo defaults to 2/s; other providers are unlimited unless configured.
+    pub fn from_env(inner: Arc<dyn PriceSource>) -> Self {
+        let var = format!("PRICE_RATE_LIMIT_{}", inner.name().to_uppercase());
+        let default = if inner.name() == "yahoo" { 2.0 } else { 0.0 };
+        let rate = parse_var(&var).unwrap_or(default);
+        Self::new(inner, rate)
+    }
+}
+
+#[async_trait]
+impl PriceSource for RateLimitedSource {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn historical_prices(&self, symbol: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<(NaiveDate, Decimal, String)>> {
+        self.limiter.acquire().await;
+        self.inner.historical_prices(symbol, start, end).await
+    }
+
+    async fn currency(&self, symbol: &str) -> Result<Option<String>> {
+        self.limiter.acquire().await;
+        self.inner.currency(symbol).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SymbolMatch>> {
+        self.limiter.acquire().await;
+        self.inner.search(query).await
+    }
+}
+
+/// How many tickers are fetched at once and how failures are retried.
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    pub concurrency: usize,
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self { concurrency: 4, retries: 3, backoff: Duration::from_millis(500) }
+    }
+}
+
+impl FetchConfig {
+    /// Reads `PRICE_FETCH_CONCURRENCY`, `PRICE_FETCH_RETRIES` and `PRICE_FETCH_BACKOFF_MS`.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            concurrency: parse_var("PRICE_FETCH_CONCURRENCY").unwrap_or(default.concurrency).max(1),
+            retries: parse_var("PRICE_FETCH_RETRIES").unwrap_or(default.retries),
+            backoff: parse_var("PRICE_FETCH_BACKOFF_MS").map(Duration::from_millis).unwrap_or(default.backoff),
+        }
+    }
+
+    /// Delay before retry number `attempt` (1-based), doubling each time.
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchStatus {
+    Ok,
+    /// The provider kept failing; bars come from the cache only
+    Stale,
+    Failed,
+}
+
+/// What happened when fetching one ticker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchOutcome {
+    pub ticker: String,
+    pub status: FetchStatus,
+    pub bars: usize,
+    pub attempts: u32,
+    pub error: Option<String>,
+}
+
+/// Fetches every ticker through the cache, `config.concurrency` at a time.
+///
+/// Tickers that fail after all retries have no entry in the returned map; every ticker has
+/// an outcome.
+pub async fn fetch_all(
+    db: &Database,
+    source: &dyn PriceSource,
+    tickers: &[String],
+    start: NaiveDate,
+    end: NaiveDate,
+    config: &FetchConfig,
+) -> (HashMap<String, Vec<(NaiveDate, Decimal, String)>>, Vec<FetchOutcome>) {
+    // Building the futures up front keeps the stream free of closures, which would make the
+    // caller's future lose `Send`
+    let fetches: Vec<_> = tickers.iter()
+        .map(|ticker| fetch_with_retries(db, source, ticker, start, end, config))
+        .collect();
+    let results: Vec<_> = stream::iter(fetches)
+        .buffer_unordered(config.concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut prices = HashMap::new();
+    let mut outcomes = Vec::new();
+    for (bars, outcome) in results {
+        if let Some(bars) = bars {
+            prices.insert(outcome.ticker.clone(), bars);
+        }
+        outcomes.push(outcome);
+    }
+    outcomes.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+    (prices, outcomes)
+}
+
+async fn fetch_with_retries(
+    db: &Database,
+    source: &dyn PriceSource,
+    ticker: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    config: &FetchConfig,
+) -> (Option<Vec<(NaiveDate, Decimal, String)>>, FetchOutcome) {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = get_cached_prices(db, source, ticker, start, end).await;
+        let error = match &result {
+            Ok(cached) => cached.fetch_error.clone(),
+            Err(e) => Some(e.to_string()),
+        };
+
+        if error.is_some() && attempts <= config.retries {
+            let delay = config.backoff_delay(attempts);
+            warn!("Fetching {} failed (attempt {}), retrying in {:?}: {}", ticker, attempts, delay, error.unwrap_or_default());
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+
+        return match result {
+            Ok(cached) => {
+                let status = if error.is_some() { FetchStatus::Stale } else { FetchStatus::Ok };
+                info!("Fetched {} prices for {}", cached.bars.len(), ticker);
+                let outcome = FetchOutcome { ticker: ticker.to_string(), status, bars: cached.bars.len(), attempts, error };
+                (Some(cached.bars), outcome)
+            }
+            Err(_) => {
+                let outcome = FetchOutcome { ticker: ticker.to_string(), status: FetchStatus::Failed, bars: 0, attempts, error };
+                (None, outcome)
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_calls() {
+        let limiter = RateLimiter::per_second(20.0);
+        let started = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        // The first call is free, the next two wait 50ms each
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let unlimited = RateLimiter::per_second(0.0);
+        let started = Instant::now();
+        for _ in 0..100 {
+            unlimited.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let config = FetchConfig { backoff: Duration::from_millis(100), ..FetchConfig::default() };
+        assert_eq!(config.backoff_delay(1), Duration::from_millis(100));
+        assert_eq!(config.backoff_delay(3), Duration::from_millis(400));
+    }
+}
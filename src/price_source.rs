@@ -0,0 +1,49 @@
+use crate::csv_prices::CsvPriceSource;
+use crate::price_fetch::RateLimitedSource;
+use crate::prices::YahooPriceSource;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// A symbol returned by `PriceSource::search`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolMatch {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub exchange: Option<String>,
+    /// Asset kind as reported by the provider, e.g. "Etf", "Equity", "MutualFund"
+    pub kind: String,
+}
+
+/// Where daily prices and symbol lookups come from.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Daily closes within `start..=end` as (date, close, currency).
+    async fn historical_prices(&self, symbol: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<(NaiveDate, Decimal, String)>>;
+
+    async fn currency(&self, symbol: &str) -> Result<Option<String>>;
+
+    async fn search(&self, query: &str) -> Result<Vec<SymbolMatch>>;
+}
+
+/// Builds the configured price source, rate limited per `RateLimitedSource::from_env`.
+///
+/// `PRICE_SOURCE` is `yahoo` (default) or `csv`; the CSV source reads `PRICE_CSV_DIR`.
+pub fn price_source_from_env() -> Result<Arc<dyn PriceSource>> {
+    let source = std::env::var("PRICE_SOURCE").unwrap_or_else(|_| "yahoo".to_string());
+    let inner: Arc<dyn PriceSource> = match source.to_lowercase().as_str() {
+        "yahoo" => Arc::new(YahooPriceSource::new()),
+        "csv" => {
+            let dir = std::env::var("PRICE_CSV_DIR")
+                .map_err(|_| anyhow!("PRICE_SOURCE=csv requires PRICE_CSV_DIR"))?;
+            Arc::new(CsvPriceSource::new(dir))
+        }
+        other => return Err(anyhow!("Unknown PRICE_SOURCE '{}' (expected yahoo or csv)", other)),
+    };
+    Ok(Arc::new(RateLimitedSource::from_env(inner)))
+}
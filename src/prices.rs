@@ -1,31 +1,56 @@
 use anyhow::{Result, anyhow};
-use chrono::NaiveDate;
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDate};
 use rust_decimal::Decimal;
-use yfinance_rs::{Ticker, YfClient, Range, Interval};
+use yfinance_rs::{search, Ticker, YfClient, Interval};
 use std::collections::HashMap;
 use std::str::FromStr;
 
-pub struct PriceFetcher {
+use crate::price_source::{PriceSource, SymbolMatch};
+
+/// Yahoo Finance via yfinance-rs.
+pub struct YahooPriceSource {
     client: YfClient,
 }
 
-impl PriceFetcher {
+impl Default for YahooPriceSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl YahooPriceSource {
     pub fn new() -> Self {
         Self {
             client: YfClient::default(),
         }
     }
+}
+
+#[async_trait]
+impl PriceSource for YahooPriceSource {
+    fn name(&self) -> &'static str {
+        "yahoo"
+    }
 
-    pub async fn get_historical_prices(
+    async fn historical_prices(
         &self,
         ticker_symbol: &str,
         start_date: NaiveDate,
         end_date: NaiveDate,
     ) -> Result<Vec<(NaiveDate, Decimal, String)>> {
         let ticker = Ticker::new(&self.client, ticker_symbol);
-        
-        // Fetch with Max range
-        let history = ticker.history(Some(Range::Max), Some(Interval::D1), false).await
+
+        // Unadjusted closes: they value the shares actually held and never change once
+        // cached, unlike dividend-adjusted history
+        let start = start_date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let end = (end_date + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let history = ticker.history_builder()
+            .between(start, end)
+            .interval(Interval::D1)
+            .auto_adjust(false)
+            .fetch()
+            .await
             .map_err(|e| anyhow!("Failed to fetch history for {}: {:?}", ticker_symbol, e))?;
         
         tracing::debug!("yfinance-rs returned {} bars for {}", history.len(), ticker_symbol);
@@ -38,14 +63,10 @@ impl PriceFetcher {
                 let close_str = bar.close.to_string();
                 // Extract currency if present (e.g., "15.53 USD" -> "USD")
                 let currency = close_str.split_whitespace().last().unwrap_or("GBP").to_string();
-                let clean_close = close_str.chars().filter(|c| c.is_digit(10) || *c == '.').collect::<String>();
+                let clean_close = close_str.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect::<String>();
                 
-                if let Ok(mut dec) = Decimal::from_str(&clean_close) {
-                    // AUTO-DETECT: LSE (.L) tickers are usually Pence (GBX) if > 250 and labeled GBP.
-                    // This handles the common Yahoo Finance inconsistency where Pence are labeled GBP.
-                    if ticker_symbol.ends_with(".L") && currency == "GBP" && dec > Decimal::from(250) {
-                        dec = dec / Decimal::from(100);
-                    }
+                // Closes are stored as quoted; the unit on the ISIN mapping decides how to read them
+                if let Ok(dec) = Decimal::from_str(&clean_close) {
                     prices.push((date, dec, currency));
                 } else {
                     if i == 0 {
@@ -68,55 +89,205 @@ impl PriceFetcher {
         Ok(prices)
     }
 
-    // Removed fetch_and_cache_prices from here to keep this Sync if possible, 
-    // or we'll just handle it in background_processor.
+    async fn currency(&self, symbol: &str) -> Result<Option<String>> {
+        let info = Ticker::new(&self.client, symbol).fast_info().await
+            .map_err(|e| anyhow!("Failed to fetch quote for {}: {:?}", symbol, e))?;
+        Ok(info.currency.map(|c| c.to_string()))
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SymbolMatch>> {
+        let response = search(&self.client, query).await?;
+        Ok(response.results.into_iter().map(|r| SymbolMatch {
+            symbol: r.symbol.to_string(),
+            name: r.name,
+            exchange: r.exchange.map(|e| e.to_string()),
+            kind: format!("{:?}", r.kind),
+        }).collect())
+    }
+}
+
+/// One FX series to apply during a conversion.
+///
+/// The series quotes the target currency per unit of the source; `invert` means the leg
+/// runs the other way and the amount is divided by the rate instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FxLeg {
+    pub ticker: String,
+    pub invert: bool,
 }
 
+/// Converts prices into the reporting base currency.
+///
+/// Pairs in the table are used directly in either direction. Any other currency is
+/// crossed through the pivot using Yahoo's `{PIVOT}{CCY}=X` series, so every ISO code
+/// works without configuration. Minor units (GBp, ZAc, ILA) are scaled to their major
+/// currency first.
 pub struct CurrencyConverter {
-    fx_config: HashMap<String, (String, bool)>, // Currency -> (FX Ticker, Multiply)
+    base: String,
+    pivot: String,
+    pairs: HashMap<(String, String), String>, // (From, To) -> FX ticker quoting To per From
+}
+
+impl Default for CurrencyConverter {
+    fn default() -> Self {
+        Self::new("GBP", "USD")
+    }
 }
 
 impl CurrencyConverter {
-    pub fn new() -> Self {
-        let mut fx_config = HashMap::new();
-        fx_config.insert("USD".to_string(), ("GBPUSD=X".to_string(), false));
-        fx_config.insert("EUR".to_string(), ("EURGBP=X".to_string(), true));
-        
+    pub fn new(base: &str, pivot: &str) -> Self {
         Self {
-            fx_config,
+            base: base.to_uppercase(),
+            pivot: pivot.to_uppercase(),
+            pairs: HashMap::new(),
         }
+        .with_pair("GBP", "USD", "GBPUSD=X")
+        .with_pair("EUR", "GBP", "EURGBP=X")
     }
 
-    pub async fn convert_to_gbp(
-        &self,
-        amount: Decimal,
-        currency: &str,
-        date: NaiveDate,
-        fx_rate: Option<Decimal>, // Pass rate externally
-    ) -> Result<Decimal> {
-        if currency == "GBP" || currency == "GBp" {
-            if currency == "GBp" {
-                return Ok(amount / Decimal::from(100));
-            }
-            return Ok(amount);
+    /// Reads `BASE_CURRENCY` (default GBP), `FX_PIVOT` (default USD) and `FX_PAIRS`, a
+    /// comma-separated list of `FROM/TO=TICKER` entries added to the built-in table.
+    pub fn from_env() -> Result<Self> {
+        let base = std::env::var("BASE_CURRENCY").unwrap_or_else(|_| "GBP".to_string());
+        let pivot = std::env::var("FX_PIVOT").unwrap_or_else(|_| "USD".to_string());
+        let mut converter = Self::new(&base, &pivot);
+        if let Ok(pairs) = std::env::var("FX_PAIRS") {
+            converter = converter.with_pairs(&pairs)?;
         }
+        Ok(converter)
+    }
+
+    pub fn with_pair(mut self, from: &str, to: &str, ticker: &str) -> Self {
+        self.pairs.insert((from.to_uppercase(), to.to_uppercase()), ticker.to_string());
+        self
+    }
 
-        if let Some((_fx_ticker, multiply)) = self.fx_config.get(currency) {
-            let rate = fx_rate.ok_or_else(|| anyhow!("FX rate required for {}", currency))?;
-            if rate.is_zero() {
-                return Err(anyhow!("FX rate is zero for {} on {}", currency, date));
+    /// Adds pairs written as `EUR/GBP=EURGBP=X,CHF/GBP=CHFGBP=X`.
+    pub fn with_pairs(mut self, spec: &str) -> Result<Self> {
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (pair, ticker) = entry.split_once('=')
+                .ok_or_else(|| anyhow!("Invalid FX pair '{}' (expected FROM/TO=TICKER)", entry))?;
+            let (from, to) = pair.split_once('/')
+                .ok_or_else(|| anyhow!("Invalid FX pair '{}' (expected FROM/TO=TICKER)", entry))?;
+            self = self.with_pair(from.trim(), to.trim(), ticker.trim());
+        }
+        Ok(self)
+    }
+
+    /// The same table and pivot converting into `base` instead.
+    pub fn with_base(mut self, base: &str) -> Self {
+        self.base = base.to_uppercase();
+        self
+    }
+
+    pub fn base_currency(&self) -> &str {
+        &self.base
+    }
+
+    /// Major currency and the factor that converts an amount into it.
+    fn major_unit(currency: &str) -> (String, Decimal) {
+        let cents = Decimal::new(1, 2);
+        match currency {
+            "GBp" | "GBX" => ("GBP".to_string(), cents),
+            "ZAc" | "ZAX" => ("ZAR".to_string(), cents),
+            "ILA" => ("ILS".to_string(), cents),
+            other => (other.to_uppercase(), Decimal::ONE),
+        }
+    }
+
+    fn leg(&self, from: &str, to: &str) -> FxLeg {
+        if let Some(ticker) = self.pairs.get(&(from.to_string(), to.to_string())) {
+            return FxLeg { ticker: ticker.clone(), invert: false };
+        }
+        if let Some(ticker) = self.pairs.get(&(to.to_string(), from.to_string())) {
+            return FxLeg { ticker: ticker.clone(), invert: true };
+        }
+        if from == self.pivot {
+            FxLeg { ticker: format!("{}{}=X", from, to), invert: false }
+        } else if to == self.pivot {
+            FxLeg { ticker: format!("{}{}=X", to, from), invert: true }
+        } else {
+            FxLeg { ticker: format!("{}{}=X", from, to), invert: false }
+        }
+    }
+
+    /// FX series needed to convert `currency` into the base currency, in order.
+    pub fn legs(&self, currency: &str) -> Vec<FxLeg> {
+        let (major, _) = Self::major_unit(currency);
+        if major == self.base {
+            return Vec::new();
+        }
+        let direct = (major.clone(), self.base.clone());
+        let reverse = (self.base.clone(), major.clone());
+        if self.pairs.contains_key(&direct) || self.pairs.contains_key(&reverse)
+            || major == self.pivot || self.base == self.pivot
+        {
+            return vec![self.leg(&major, &self.base)];
+        }
+        vec![self.leg(&major, &self.pivot), self.leg(&self.pivot, &self.base)]
+    }
+
+    /// Converts `amount` into the base currency; `rate` returns the value of a leg's
+    /// series on the conversion date.
+    pub fn convert(&self, amount: Decimal, currency: &str, rate: impl Fn(&FxLeg) -> Option<Decimal>) -> Result<Decimal> {
+        let (_, scale) = Self::major_unit(currency);
+        let mut converted = amount * scale;
+        for leg in self.legs(currency) {
+            let r = rate(&leg).ok_or_else(|| anyhow!("FX rate {} required for {}", leg.ticker, currency))?;
+            if r.is_zero() {
+                return Err(anyhow!("FX rate {} is zero", leg.ticker));
             }
-            if *multiply {
-                Ok(amount * rate)
+            if leg.invert {
+                converted /= r;
             } else {
-                Ok(amount / rate)
+                converted *= r;
             }
-        } else {
-            Err(anyhow!("Unknown currency: {}", currency))
+        }
+        Ok(converted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn rates(leg: &FxLeg) -> Option<Decimal> {
+        match leg.ticker.as_str() {
+            "GBPUSD=X" => Some(dec!(1.25)),
+            "EURGBP=X" => Some(dec!(0.85)),
+            "USDJPY=X" => Some(dec!(150)),
+            "USDEUR=X" => Some(dec!(0.9)),
+            _ => None,
         }
     }
-    
-    pub fn get_fx_ticker(&self, currency: &str) -> Option<String> {
-        self.fx_config.get(currency).map(|(t, _)| t.clone())
+
+    #[test]
+    fn test_convert_to_gbp_base() {
+        let fx = CurrencyConverter::default();
+        assert_eq!(fx.convert(dec!(250), "GBp", rates).unwrap(), dec!(2.5));
+        assert_eq!(fx.convert(dec!(125), "USD", rates).unwrap(), dec!(100));
+        assert_eq!(fx.convert(dec!(100), "EUR", rates).unwrap(), dec!(85));
+
+        // Crossed through USD: 15000 JPY = 100 USD = 80 GBP
+        assert_eq!(fx.legs("JPY"), vec![
+            FxLeg { ticker: "USDJPY=X".to_string(), invert: true },
+            FxLeg { ticker: "GBPUSD=X".to_string(), invert: true },
+        ]);
+        assert_eq!(fx.convert(dec!(15000), "JPY", rates).unwrap(), dec!(80));
+
+        assert!(fx.convert(dec!(1), "CHF", rates).is_err());
+    }
+
+    #[test]
+    fn test_convert_to_other_base() {
+        let fx = CurrencyConverter::new("EUR", "USD").with_pairs("CHF/EUR=CHFEUR=X").unwrap();
+        assert_eq!(fx.legs("CHF"), vec![FxLeg { ticker: "CHFEUR=X".to_string(), invert: false }]);
+        // GBP prices use the built-in EURGBP=X pair inverted
+        assert_eq!(fx.convert(dec!(85), "GBP", rates).unwrap(), dec!(100));
+        // USD is the pivot, so one leg from the pivot series
+        assert_eq!(fx.convert(dec!(100), "USD", rates).unwrap(), dec!(90));
+
+        assert!(CurrencyConverter::default().with_pairs("CHFGBP").is_err());
     }
 }
@@ -0,0 +1,66 @@
+use rust_decimal::Decimal;
+
+/// Canonical spelling of a currency or quote unit; pence are always "GBp".
+pub fn normalise_currency(currency: &str) -> String {
+    let trimmed = currency.trim();
+    if trimmed.is_empty() {
+        return "GBP".to_string();
+    }
+    if trimmed == "GBp" || trimmed.eq_ignore_ascii_case("GBX") {
+        return "GBp".to_string();
+    }
+    trimmed.to_uppercase()
+}
+
+fn is_sterling(unit: &str) -> bool {
+    unit == "GBP" || unit == "GBp"
+}
+
+/// Works out the unit a security's provider prices are quoted in.
+///
+/// `provider_currency` is the provider's own label, `provider_close` a close near the date
+/// of a statement trade and `trade_price` that trade's share price in GBP. Providers often
+/// label pence quotes as GBP, so for sterling labels the close is compared with the trade
+/// price: about 100 times larger means pence. Returns `None` when there is nothing to go on.
+pub fn detect_quote_unit(
+    provider_currency: Option<&str>,
+    provider_close: Option<Decimal>,
+    trade_price: Option<Decimal>,
+) -> Option<String> {
+    let label = provider_currency.map(normalise_currency);
+
+    if label.as_deref().is_none_or(is_sterling)
+        && let (Some(close), Some(price)) = (provider_close, trade_price)
+        && !price.is_zero()
+    {
+        let ratio = close / price;
+        if ratio >= Decimal::from(50) && ratio <= Decimal::from(200) {
+            return Some("GBp".to_string());
+        }
+        if ratio >= Decimal::new(5, 1) && ratio <= Decimal::from(2) {
+            return Some("GBP".to_string());
+        }
+    }
+
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_detect_quote_unit() {
+        // Pence mislabelled as GBP, whatever the magnitude
+        assert_eq!(detect_quote_unit(Some("GBP"), Some(dec!(101.2)), Some(dec!(1.01))).as_deref(), Some("GBp"));
+        // An expensive ETF really quoted in pounds
+        assert_eq!(detect_quote_unit(Some("GBP"), Some(dec!(412.5)), Some(dec!(410))).as_deref(), Some("GBP"));
+        assert_eq!(detect_quote_unit(None, Some(dec!(7150)), Some(dec!(71.60))).as_deref(), Some("GBp"));
+
+        // Foreign quotes and bare metadata are taken as labelled
+        assert_eq!(detect_quote_unit(Some("USD"), Some(dec!(190)), Some(dec!(150))).as_deref(), Some("USD"));
+        assert_eq!(detect_quote_unit(Some("GBX"), None, None).as_deref(), Some("GBp"));
+        assert_eq!(detect_quote_unit(None, None, Some(dec!(10))), None);
+    }
+}
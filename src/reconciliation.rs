@@ -0,0 +1,273 @@
+use crate::cash_balance::daily_cash_balances;
+use crate::models::{AccountType, CashRecord, TradingRecord, TransactionKind};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Differences below a penny are rounding, not discrepancies
+const CASH_TOLERANCE: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+
+/// A date on which the cash counted in the daily value stops agreeing with the statement's
+/// `Balance` column.
+///
+/// Only dates where the difference changes are reported, so an opening balance missing from
+/// the statement shows up once rather than on every later date.
+#[derive(Debug, Clone, Serialize)]
+pub struct CashDiscrepancy {
+    pub date: NaiveDate,
+    pub statement_balance: Decimal,
+    /// As reconstructed by `cash_balance::daily_cash_balances`
+    pub computed_balance: Decimal,
+    /// statement - computed
+    pub difference: Decimal,
+    /// The statement rows for the date
+    pub rows: Vec<CashRecord>,
+}
+
+/// A disposal of more units than the account held at the time.
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldingIssue {
+    pub isin: String,
+    pub ticker: Option<String>,
+    pub trade_date_time: NaiveDateTime,
+    pub transaction_type: TransactionKind,
+    pub quantity: Decimal,
+    pub held_before: Decimal,
+    /// Negative: the position the statements imply after the trade
+    pub held_after: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountReconciliation {
+    pub account: AccountType,
+    pub cash_rows: usize,
+    pub trades: usize,
+    pub cash_discrepancies: Vec<CashDiscrepancy>,
+    pub holding_issues: Vec<HoldingIssue>,
+}
+
+impl AccountReconciliation {
+    pub fn is_clean(&self) -> bool {
+        self.cash_discrepancies.is_empty() && self.holding_issues.is_empty()
+    }
+}
+
+/// Checks each account's reconstructed cash against its statement balances and its trades
+/// for oversold positions.
+pub fn reconcile(cash: &[CashRecord], trades: &[TradingRecord]) -> Vec<AccountReconciliation> {
+    let accounts: BTreeSet<AccountType> = cash.iter().map(|r| r.account_type)
+        .chain(trades.iter().map(|t| t.account_type))
+        .collect();
+    // Only rows with a balance of their own can be checked
+    let dates: Vec<NaiveDate> = cash.iter()
+        .filter(|r| r.balance.is_some())
+        .map(|r| r.date)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let computed = daily_cash_balances(cash, trades, &dates);
+
+    accounts.into_iter()
+        .map(|account| {
+            let rows: Vec<&CashRecord> = cash.iter().filter(|r| r.account_type == account).collect();
+            let account_trades: Vec<&TradingRecord> = trades.iter().filter(|t| t.account_type == account).collect();
+            let balances: BTreeMap<NaiveDate, Decimal> = match computed.get(&account) {
+                Some(series) => dates.iter().copied().zip(series.iter().copied()).collect(),
+                None => BTreeMap::new(),
+            };
+            AccountReconciliation {
+                account,
+                cash_rows: rows.len(),
+                trades: account_trades.len(),
+                cash_discrepancies: reconcile_cash(&rows, &balances),
+                holding_issues: find_oversold(&account_trades),
+            }
+        })
+        .collect()
+}
+
+/// Compares the statement's balances with the end-of-day `computed` balances.
+fn reconcile_cash(rows: &[&CashRecord], computed: &BTreeMap<NaiveDate, Decimal>) -> Vec<CashDiscrepancy> {
+    let mut by_date: BTreeMap<NaiveDate, Vec<&CashRecord>> = BTreeMap::new();
+    for r in rows {
+        by_date.entry(r.date).or_default().push(r);
+    }
+
+    let mut discrepancies = Vec::new();
+    let mut previous_difference = Decimal::ZERO;
+    for (date, day_rows) in by_date {
+        let Some(&computed) = computed.get(&date) else {
+            continue;
+        };
+        let Some(statement_balance) = closing_balance(&day_rows) else {
+            continue;
+        };
+        let difference = statement_balance - computed;
+        if (difference - previous_difference).abs() >= CASH_TOLERANCE {
+            discrepancies.push(CashDiscrepancy {
+                date,
+                statement_balance,
+                computed_balance: computed,
+                difference,
+                rows: day_rows.into_iter().cloned().collect(),
+            });
+        }
+        previous_difference = difference;
+    }
+    discrepancies
+}
+
+/// The balance after the last of a day's rows.
+///
+/// Statements list same-day rows in either order, so the closing row is found from the
+/// running-balance chain: it is the one whose balance no other row of the day starts from.
+/// When the chain is broken that can be several rows; the last one listed is taken.
+fn closing_balance(day_rows: &[&CashRecord]) -> Option<Decimal> {
+    let balanced: Vec<(Decimal, Decimal)> = day_rows.iter()
+        .filter_map(|r| r.balance.map(|b| (b - r.net_flow, b)))
+        .collect();
+    let closing = balanced.iter()
+        .enumerate()
+        .filter(|(i, (_, after))| !balanced.iter().enumerate().any(|(j, (before, _))| j != *i && before == after))
+        .map(|(_, (_, after))| *after)
+        .next_back();
+    closing.or_else(|| balanced.last().map(|(_, after)| *after))
+}
+
+/// Disposals in one account's `trades` of more units than were held at the time.
+pub fn find_oversold(trades: &[&TradingRecord]) -> Vec<HoldingIssue> {
+    let mut sorted = trades.to_vec();
+    sorted.sort_by_key(|t| t.trade_date_time);
+
+    let mut held: HashMap<&str, Decimal> = HashMap::new();
+    let mut issues = Vec::new();
+    for t in sorted {
+        let position = held.entry(t.security_isin.as_str()).or_default();
+        let before = *position;
+        *position += t.transaction_type.holding_change(t.quantity);
+        if *position < Decimal::ZERO && *position < before {
+            issues.push(HoldingIssue {
+                isin: t.security_isin.clone(),
+                ticker: t.ticker.clone(),
+                trade_date_time: t.trade_date_time,
+                transaction_type: t.transaction_type,
+                quantity: t.quantity,
+                held_before: before,
+                held_after: *position,
+            });
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CashActivityKind;
+    use rust_decimal_macros::dec;
+
+    fn d(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn cash(day: u32, activity: &str, net_flow: Decimal, balance: Decimal) -> CashRecord {
+        CashRecord {
+            date: d(day),
+            activity: activity.to_string(),
+            credit: (net_flow > Decimal::ZERO).then_some(net_flow),
+            debit: (net_flow < Decimal::ZERO).then(|| net_flow.abs()),
+            balance: Some(balance),
+            account_type: AccountType::ISA,
+            net_flow,
+            kind: CashActivityKind::classify(activity, net_flow),
+        }
+    }
+
+    fn trade(kind: TransactionKind, day: u32, quantity: Decimal) -> TradingRecord {
+        let dt = d(day).and_hms_opt(10, 0, 0).unwrap();
+        TradingRecord {
+            security_isin: "IE00BK5BQT80".to_string(),
+            transaction_type: kind,
+            quantity,
+            share_price: dec!(100),
+            total_trade_value: quantity * dec!(100),
+            trade_date_time: dt,
+            settlement_date: dt,
+            broker: "Winterflood".to_string(),
+            account_type: AccountType::ISA,
+            ticker: Some("VWRP.L".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let records = vec![
+            cash(2, "Payment Received", dec!(1000), dec!(1000)),
+            // Same-day rows listed newest first: the fee, then the buy
+            cash(3, "Buy: Vanguard FTSE All-World", dec!(-500), dec!(490)),
+            cash(3, "Management Fee", dec!(-10), dec!(990)),
+            // A dividend row missing from the upload leaves a lasting £4.20 gap
+            cash(5, "Buy: Vanguard FTSE All-World", dec!(-400), dec!(94.20)),
+            cash(6, "Interest", dec!(0.80), dec!(95)),
+        ];
+        let trades = vec![
+            trade(TransactionKind::Buy, 3, dec!(5)),
+            trade(TransactionKind::Sell, 4, dec!(3)),
+            trade(TransactionKind::Sell, 5, dec!(4)),
+        ];
+
+        let report = reconcile(&records, &trades);
+        assert_eq!(report.len(), 1);
+        let isa = &report[0];
+        assert!(!isa.is_clean());
+
+        assert_eq!(isa.cash_discrepancies.len(), 1);
+        assert_eq!(isa.cash_discrepancies[0].date, d(5));
+        assert_eq!(isa.cash_discrepancies[0].difference, dec!(4.20));
+
+        assert_eq!(isa.holding_issues.len(), 1);
+        assert_eq!(isa.holding_issues[0].held_before, dec!(2));
+        assert_eq!(isa.holding_issues[0].held_after, dec!(-2));
+    }
+
+    // The statement lists no settlement rows, but its balance reflects the £500 buy
+    fn without_settlement_rows() -> Vec<CashRecord> {
+        vec![
+            cash(2, "Payment Received", dec!(1000), dec!(1000)),
+            cash(6, "Interest", dec!(0.80), dec!(500.80)),
+        ]
+    }
+
+    #[test]
+    fn test_trade_settlements_stand_in_for_settlement_rows() {
+        let trades = vec![trade(TransactionKind::Buy, 3, dec!(5))];
+        assert!(reconcile(&without_settlement_rows(), &trades)[0].is_clean());
+    }
+
+    #[test]
+    fn test_mispriced_trade_breaks_the_balance() {
+        // Valued £10 above what the account paid
+        let trades = vec![trade(TransactionKind::Buy, 3, dec!(5.1))];
+        let report = reconcile(&without_settlement_rows(), &trades);
+        assert_eq!(report[0].cash_discrepancies.len(), 1);
+        assert_eq!(report[0].cash_discrepancies[0].date, d(6));
+        assert_eq!(report[0].cash_discrepancies[0].difference, dec!(10));
+    }
+
+    #[test]
+    fn test_intermediate_balance_does_not_hide_a_break() {
+        let records = vec![
+            cash(2, "Payment Received", dec!(1000), dec!(1000)),
+            // Listed newest first; only the fee's balance closes the day
+            cash(6, "Management Fee", dec!(-0.50), dec!(500.30)),
+            cash(6, "Interest", dec!(0.80), dec!(500.80)),
+        ];
+        // The trades are valued £0.50 short, so the computed close matches the interest row
+        let trades = vec![trade(TransactionKind::Buy, 3, dec!(4.995))];
+        let report = reconcile(&records, &trades);
+        assert_eq!(report[0].cash_discrepancies.len(), 1);
+        assert_eq!(report[0].cash_discrepancies[0].statement_balance, dec!(500.30));
+        assert_eq!(report[0].cash_discrepancies[0].difference, dec!(-0.50));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::env::parse_var;
+use crate::period_returns::flow_adjusted_returns;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// The daily series covers calendar days, weekends included, so that is what annualises
+const PERIODS_PER_YEAR: f64 = 365.0;
+
+/// Annual risk-free rate from `RISK_FREE_RATE` (e.g. 0.04 for 4%; default 0).
+pub fn risk_free_rate_from_env() -> f64 {
+    parse_var("RISK_FREE_RATE").unwrap_or(0.0)
+}
+
+/// The deepest fall of the flow-adjusted index from a previous high.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Drawdown {
+    pub peak_date: NaiveDate,
+    pub trough_date: NaiveDate,
+    /// First close back at the peak; none while still under water
+    pub recovery_date: Option<NaiveDate>,
+    /// Negative, e.g. -0.2 for a 20% fall
+    pub depth: f64,
+    /// Peak to recovery, or to the last date when not recovered
+    pub duration_days: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskMetrics {
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    /// Daily returns the figures are based on
+    pub observations: usize,
+    pub risk_free_rate: f64,
+    pub annualised_return: f64,
+    pub volatility: f64,
+    /// Of returns below the risk-free rate
+    pub downside_deviation: f64,
+    /// None when there is no volatility to divide by
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    pub max_drawdown: Option<Drawdown>,
+}
+
+/// Growth of 1 from the first day, compounding `returns`.
+pub fn growth_index(returns: &[f64]) -> Vec<f64> {
+    returns.iter()
+        .scan(1.0, |level, r| {
+            *level *= 1.0 + r;
+            Some(*level)
+        })
+        .collect()
+}
+
+/// How far below its running high the index is on each day; zero at a new high.
+pub fn drawdown_series(index: &[f64]) -> Vec<f64> {
+    let mut peak = f64::MIN;
+    index.iter()
+        .map(|&level| {
+            peak = peak.max(level);
+            if peak > 0.0 { level / peak - 1.0 } else { 0.0 }
+        })
+        .collect()
+}
+
+/// Windows, in days, for the rolling series
+pub const ROLLING_WINDOWS: [usize; 3] = [30, 90, 365];
+
+/// Return over the `window` days up to each day, from a daily growth index.
+///
+/// Days whose window reaches back before `start` (the first funded day) have none.
+pub fn rolling_returns(index: &[f64], start: usize, window: usize) -> Vec<Option<f64>> {
+    (0..index.len())
+        .map(|i| {
+            let from = i.checked_sub(window).filter(|from| *from >= start)?;
+            (index[from] > 0.0).then(|| index[i] / index[from] - 1.0)
+        })
+        .collect()
+}
+
+/// Annualised volatility of the daily `returns` in the `window` days up to each day.
+pub fn rolling_volatility(returns: &[f64], start: usize, window: usize) -> Vec<Option<f64>> {
+    (0..returns.len())
+        .map(|i| {
+            let from = i.checked_sub(window).filter(|from| *from >= start && window > 1)?;
+            let slice = &returns[from + 1..=i];
+            let n = slice.len() as f64;
+            let mean = slice.iter().sum::<f64>() / n;
+            let variance = slice.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            Some(variance.sqrt() * PERIODS_PER_YEAR.sqrt())
+        })
+        .collect()
+}
+
+/// Index of the first day the portfolio holds anything; returns before it are meaningless.
+pub fn first_funded_day(values: &[Decimal]) -> Option<usize> {
+    values.iter().position(|v| *v > Decimal::ZERO)
+}
+
+/// Volatility, Sharpe, Sortino and the maximum drawdown of the flow-adjusted daily returns.
+///
+/// `values` and external `flows` are aligned with `dates`; days before the first funded one
+/// are ignored.
+pub fn calculate_risk_metrics(dates: &[NaiveDate], values: &[Decimal], flows: &[Decimal], risk_free_rate: f64) -> RiskMetrics {
+    let mut metrics = RiskMetrics {
+        start_date: None,
+        end_date: None,
+        observations: 0,
+        risk_free_rate,
+        annualised_return: 0.0,
+        volatility: 0.0,
+        downside_deviation: 0.0,
+        sharpe: None,
+        sortino: None,
+        max_drawdown: None,
+    };
+    let Some(start) = first_funded_day(values).filter(|_| dates.len() == values.len() && flows.len() == values.len()) else {
+        return metrics;
+    };
+    let dates = &dates[start..];
+    let returns: Vec<f64> = flow_adjusted_returns(&values[start..], &flows[start..]).into_iter().skip(1).collect();
+    metrics.start_date = dates.first().copied();
+    metrics.end_date = dates.last().copied();
+    metrics.observations = returns.len();
+    if returns.len() < 2 {
+        return metrics;
+    }
+
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let daily_risk_free = (1.0 + risk_free_rate).powf(1.0 / PERIODS_PER_YEAR) - 1.0;
+    let downside = returns.iter()
+        .map(|r| (r - daily_risk_free).min(0.0).powi(2))
+        .sum::<f64>() / n;
+
+    let growth = returns.iter().fold(1.0, |acc, r| acc * (1.0 + r));
+    metrics.annualised_return = if growth > 0.0 { growth.powf(PERIODS_PER_YEAR / n) - 1.0 } else { -1.0 };
+    metrics.volatility = variance.sqrt() * PERIODS_PER_YEAR.sqrt();
+    metrics.downside_deviation = downside.sqrt() * PERIODS_PER_YEAR.sqrt();
+    let excess = (mean - daily_risk_free) * PERIODS_PER_YEAR;
+    metrics.sharpe = (metrics.volatility > 0.0).then(|| excess / metrics.volatility);
+    metrics.sortino = (metrics.downside_deviation > 0.0).then(|| excess / metrics.downside_deviation);
+
+    // The index starts at 1 on the first funded day, before the first return
+    let index: Vec<f64> = std::iter::once(1.0).chain(growth_index(&returns)).collect();
+    metrics.max_drawdown = max_drawdown(dates, &index);
+    metrics
+}
+
+fn max_drawdown(dates: &[NaiveDate], index: &[f64]) -> Option<Drawdown> {
+    let underwater = drawdown_series(index);
+    let (trough, &depth) = underwater.iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))?;
+    if depth >= 0.0 {
+        return None;
+    }
+    let peak = (0..trough).rev().find(|&i| underwater[i] == 0.0).unwrap_or(0);
+    let recovery = (trough + 1..index.len()).find(|&i| index[i] >= index[peak]);
+    let last = recovery.unwrap_or(index.len() - 1);
+    Some(Drawdown {
+        peak_date: dates[peak],
+        trough_date: dates[trough],
+        recovery_date: recovery.map(|i| dates[i]),
+        depth,
+        duration_days: (dates[last] - dates[peak]).num_days(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_risk_metrics() {
+        let dates: Vec<NaiveDate> = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().iter_days().take(8).collect();
+        // Nothing held on day one; a 100 deposit, a rise, a 20% fall, another 50 paid in
+        // (not a gain), a further 10% fall and a recovery past the old high
+        let values = vec![dec!(0), dec!(100), dec!(110), dec!(88), dec!(138), dec!(124.2), dec!(180), dec!(190)];
+        let flows = vec![dec!(0), dec!(100), dec!(0), dec!(0), dec!(50), dec!(0), dec!(0), dec!(0)];
+        let metrics = calculate_risk_metrics(&dates, &values, &flows, 0.0);
+
+        assert_eq!(metrics.start_date, Some(dates[1]));
+        assert_eq!(metrics.observations, 6);
+        assert!(metrics.volatility > 0.0);
+        assert!(metrics.downside_deviation > 0.0);
+        assert!(metrics.sharpe.is_some() && metrics.sortino.is_some());
+
+        let drawdown = metrics.max_drawdown.unwrap();
+        assert_eq!(drawdown.peak_date, dates[2]);
+        assert_eq!(drawdown.trough_date, dates[5]);
+        assert_eq!(drawdown.recovery_date, Some(dates[6]));
+        assert!((drawdown.depth - (0.8 * 0.9 - 1.0)).abs() < 1e-9, "depth {}", drawdown.depth);
+
+        let index = growth_index(&flow_adjusted_returns(&values, &flows));
+        assert_eq!(drawdown_series(&index)[5], drawdown.depth);
+        let rolling = rolling_returns(&index, 1, 3);
+        assert_eq!(rolling[3], None);
+        assert!((rolling[4].unwrap() - (0.88 - 1.0)).abs() < 1e-9);
+        let volatility = rolling_volatility(&flow_adjusted_returns(&values, &flows), 1, 3);
+        assert_eq!(volatility[3], None);
+        assert!(volatility[4].unwrap() > 0.0);
+
+        let flat = calculate_risk_metrics(&dates, &[dec!(100); 8], &[dec!(0); 8], 0.0);
+        assert!(flat.sharpe.is_none());
+        assert!(flat.max_drawdown.is_none());
+    }
+}
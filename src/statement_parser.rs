@@ -0,0 +1,262 @@
+use crate::freetrade::FreetradeParser;
+use crate::merge_csv::{detect_statement, extract_account_type, parse_cash_files, parse_trading_files, DetectionConfidence, FileType, ParseIssue, StatementInfo};
+use crate::models::{AccountType, CashActivityKind, CashRecord, TradingRecord, TransactionKind};
+use crate::trading212::Trading212Parser;
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// Trades and cash rows normalised from one broker export.
+#[derive(Debug, Default)]
+pub struct ParsedStatement {
+    pub trades: Vec<TradingRecord>,
+    pub cash: Vec<CashRecord>,
+    pub issues: Vec<ParseIssue>,
+}
+
+/// Currency of every amount in `TradingRecord`s and `CashRecord`s.
+pub const STATEMENT_CURRENCY: &str = "GBP";
+
+/// Maps one broker's CSV export onto `TradingRecord`s and `CashRecord`s.
+///
+/// Parsers normalise to InvestEngine's conventions: `security_isin` is
+/// "Name / ISIN XXXXXXXXXXXX", amounts are in GBP, and external cash flows use the
+/// "Payment Received" / "Withdrawal" activity names.
+pub trait StatementParser: Send + Sync {
+    fn broker(&self) -> &'static str;
+
+    /// Returns `None` if the file does not look like this broker's export.
+    fn detect(&self, filename: &str, content: &str) -> Option<StatementInfo>;
+
+    fn parse(&self, filename: &str, content: &str, info: &StatementInfo) -> ParsedStatement;
+}
+
+pub struct ParserRegistry {
+    parsers: Vec<Box<dyn StatementParser>>,
+}
+
+impl Default for ParserRegistry {
+    fn default() -> Self {
+        Self {
+            parsers: vec![
+                Box::new(InvestEngineParser),
+                Box::new(Trading212Parser),
+                Box::new(FreetradeParser),
+            ],
+        }
+    }
+}
+
+impl ParserRegistry {
+    /// Picks the parser with the most confident detection; earlier registrations win ties.
+    pub fn detect(&self, filename: &str, content: &str) -> Option<(&dyn StatementParser, StatementInfo)> {
+        let mut best: Option<(&dyn StatementParser, StatementInfo)> = None;
+        for parser in &self.parsers {
+            if let Some(info) = parser.detect(filename, content)
+                && best.as_ref().is_none_or(|(_, b)| info.confidence > b.confidence)
+            {
+                best = Some((parser.as_ref(), info));
+            }
+        }
+        best
+    }
+
+    /// Detects and parses one file, returning the broker that handled it.
+    pub fn parse(&self, filename: &str, content: &str) -> Option<(&'static str, StatementInfo, ParsedStatement)> {
+        let (parser, info) = self.detect(filename, content)?;
+        let parsed = parser.parse(filename, content, &info);
+        Some((parser.broker(), info, parsed))
+    }
+}
+
+pub struct InvestEngineParser;
+
+impl StatementParser for InvestEngineParser {
+    fn broker(&self) -> &'static str {
+        "InvestEngine"
+    }
+
+    fn detect(&self, filename: &str, content: &str) -> Option<StatementInfo> {
+        // Always answers: InvestEngine is the fallback for unrecognised files
+        Some(detect_statement(filename, content))
+    }
+
+    fn parse(&self, filename: &str, content: &str, info: &StatementInfo) -> ParsedStatement {
+        let file = vec![(filename.to_string(), content.to_string())];
+        match info.file_type {
+            FileType::Cash => {
+                let parsed = parse_cash_files(file);
+                ParsedStatement { trades: Vec::new(), cash: parsed.records, issues: parsed.issues }
+            }
+            _ => {
+                let parsed = parse_trading_files(file);
+                ParsedStatement { trades: parsed.records, cash: Vec::new(), issues: parsed.issues }
+            }
+        }
+    }
+}
+
+/// Recognises a single-table broker export by the columns of its header row.
+///
+/// These exports do not name the account, so it comes from the filename; an unknown
+/// account keeps the detection at medium confidence.
+pub(crate) fn detect_by_header(filename: &str, content: &str, required: &[&str]) -> Option<StatementInfo> {
+    let header = content.trim_start_matches('\u{feff}').lines().map(str::trim).find(|l| !l.is_empty())?;
+    let columns: Vec<&str> = header.split(',').map(|c| c.trim().trim_matches('"')).collect();
+    if !required.iter().all(|r| columns.contains(r)) {
+        return None;
+    }
+
+    let account_type = extract_account_type(filename);
+    let confidence = if account_type == AccountType::Other {
+        DetectionConfidence::Medium
+    } else {
+        DetectionConfidence::High
+    };
+    Some(StatementInfo { file_type: FileType::Combined, account_type, portfolio: None, confidence })
+}
+
+/// Header-indexed access to a broker CSV row that reports failures as `ParseIssue`s.
+pub(crate) struct BrokerRow<'a> {
+    pub file: &'a str,
+    pub line: u64,
+    pub headers: &'a csv::StringRecord,
+    pub record: &'a csv::StringRecord,
+}
+
+impl BrokerRow<'_> {
+    pub fn get(&self, column: &str) -> &str {
+        self.headers.iter().position(|h| h == column)
+            .and_then(|i| self.record.get(i))
+            .unwrap_or_default()
+    }
+
+    pub fn issue(&self, column: &str, reason: impl Into<String>) -> ParseIssue {
+        ParseIssue {
+            file: self.file.to_string(),
+            line: self.line,
+            column: Some(column.to_string()),
+            raw_value: Some(self.get(column).to_string()),
+            reason: reason.into(),
+        }
+    }
+
+    /// Parses an optional amount, stripping currency symbols and thousands separators.
+    pub fn optional_decimal(&self, column: &str) -> Result<Option<Decimal>, ParseIssue> {
+        let clean = self.get(column).replace(['£', '$', '€', ','], "");
+        let clean = clean.trim();
+        if clean.is_empty() {
+            return Ok(None);
+        }
+        Decimal::from_str(clean)
+            .map(Some)
+            .map_err(|_| self.issue(column, "Invalid number"))
+    }
+
+    pub fn decimal(&self, column: &str) -> Result<Decimal, ParseIssue> {
+        self.optional_decimal(column)?
+            .ok_or_else(|| self.issue(column, "Missing value"))
+    }
+
+    pub fn datetime(&self, column: &str, formats: &[&str]) -> Result<NaiveDateTime, ParseIssue> {
+        let raw = self.get(column);
+        formats.iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(raw, f).ok())
+            .ok_or_else(|| self.issue(column, "Invalid datetime"))
+    }
+}
+
+/// Reads a headed CSV export and hands each row to `handle`, collecting row issues.
+pub(crate) fn for_each_row(
+    filename: &str,
+    content: &str,
+    mut handle: impl FnMut(&BrokerRow) -> Result<(), ParseIssue>,
+) -> Vec<ParseIssue> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        // Detection already looks past the BOM Excel adds, so the header must too
+        .from_reader(Cursor::new(content.trim_start_matches('\u{feff}')));
+
+    let mut issues = Vec::new();
+    let headers = match rdr.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => {
+            issues.push(ParseIssue {
+                file: filename.to_string(),
+                line: 1,
+                column: None,
+                raw_value: None,
+                reason: format!("Unreadable header row: {}", e),
+            });
+            return issues;
+        }
+    };
+
+    for result in rdr.records() {
+        match result {
+            Ok(record) => {
+                let row = BrokerRow {
+                    file: filename,
+                    line: record.position().map(|p| p.line()).unwrap_or_default(),
+                    headers: &headers,
+                    record: &record,
+                };
+                if let Err(issue) = handle(&row) {
+                    issues.push(issue);
+                }
+            }
+            Err(e) => issues.push(ParseIssue {
+                file: filename.to_string(),
+                line: e.position().map(|p| p.line()).unwrap_or_default(),
+                column: None,
+                raw_value: None,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    issues
+}
+
+/// Builds a `TradingRecord` with InvestEngine's field conventions.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn normalised_trade(
+    name: &str,
+    isin: &str,
+    transaction_type: TransactionKind,
+    quantity: Decimal,
+    share_price: Decimal,
+    total_trade_value: Decimal,
+    trade_date_time: NaiveDateTime,
+    broker: &str,
+    account_type: AccountType,
+) -> TradingRecord {
+    TradingRecord {
+        security_isin: format!("{} / ISIN {}", name, isin),
+        transaction_type,
+        quantity,
+        share_price,
+        total_trade_value,
+        trade_date_time,
+        settlement_date: trade_date_time.date().and_hms_opt(0, 0, 0).unwrap(),
+        broker: broker.to_string(),
+        account_type,
+        ticker: None,
+    }
+}
+
+/// Builds a `CashRecord` from a signed amount (positive = money in).
+pub(crate) fn normalised_cash(date: NaiveDateTime, activity: &str, amount: Decimal, account_type: AccountType) -> CashRecord {
+    CashRecord {
+        date: date.date(),
+        activity: activity.to_string(),
+        credit: (amount > Decimal::ZERO).then_some(amount),
+        debit: (amount < Decimal::ZERO).then(|| amount.abs()),
+        balance: None,
+        account_type,
+        net_flow: amount,
+        kind: CashActivityKind::classify(activity, amount),
+    }
+}
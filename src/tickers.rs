@@ -1,53 +1,160 @@
 use anyhow::Result;
-use yfinance_rs::{YfClient, search};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use crate::price_source::{PriceSource, SymbolMatch};
 
-pub async fn search_ticker_for_isin(security_name: &str, isin: &str) -> Result<Option<String>> {
-    let client = YfClient::default();
+/// Most candidates whose currency is looked up; the rest keep `currency: None`
+const CURRENCY_LOOKUPS: usize = 3;
 
-    // Primary: Search with security name
-    if let Some(symbol) = perform_search(&client, security_name, isin).await? {
-        return Ok(Some(symbol));
-    }
+/// Added for a sterling quote, so a currency lookup can only lift a candidate this far
+const STERLING_SCORE: u32 = 10;
 
-    // Fallback: Search with ISIN
-    if let Some(symbol) = perform_search(&client, isin, isin).await? {
-        return Ok(Some(symbol));
-    }
+/// Asset kinds that can be held and priced; indices, futures and the like are dropped
+const PLAUSIBLE_KINDS: [&str; 4] = ["Etf", "Equity", "MutualFund", "Currency"];
+
+/// A symbol that may quote the security behind an ISIN.
+#[derive(Debug, Clone, Serialize)]
+pub struct TickerCandidate {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub exchange: Option<String>,
+    pub kind: String,
+    pub currency: Option<String>,
+    /// Whether searching for the ISIN itself returned this symbol
+    pub matched_isin: bool,
+    /// 0-100, higher is a better match
+    pub score: u32,
+}
 
-    Ok(None)
+pub async fn search_ticker_for_isin(source: &dyn PriceSource, security_name: &str, isin: &str) -> Result<Option<String>> {
+    let candidates = rank_ticker_candidates(source, security_name, isin).await?;
+    Ok(candidates.into_iter().next().map(|c| c.symbol))
 }
 
-async fn perform_search(client: &YfClient, query: &str, isin: &str) -> Result<Option<String>> {
-    let response = search(client, query).await?;
-    
-    // Prefer LSE exchange or .L suffix, and ETF/Equity types
-    // SearchResult from paft has: symbol, name, exchange, kind
-    
-    let lse_candidates: Vec<_> = response.results.iter()
-        .filter(|r| {
-            let sym_str = r.symbol.to_string();
-            let exch_str = r.exchange.as_ref().map(|e| e.to_string()).unwrap_or_default();
-            
-            (exch_str.contains("LSE") || sym_str.ends_with(".L")) && 
-            (format!("{:?}", r.kind) == "Etf" || format!("{:?}", r.kind) == "Equity")
+/// Every plausible symbol for `isin`, best first.
+///
+/// Searches by ISIN and by security name, then scores each hit on the ISIN match, asset
+/// kind, London listing, sterling quote and name similarity (see `compute_score`).
+pub async fn rank_ticker_candidates(source: &dyn PriceSource, security_name: &str, isin: &str) -> Result<Vec<TickerCandidate>> {
+    let by_isin = perform_search(source, isin).await?;
+    let by_name = perform_search(source, security_name).await?;
+
+    let isin_symbols: HashSet<String> = by_isin.iter().map(|r| r.symbol.clone()).collect();
+    let mut seen = HashSet::new();
+    let mut candidates: Vec<TickerCandidate> = by_isin.into_iter()
+        .chain(by_name)
+        .filter(|r| r.symbol != isin && PLAUSIBLE_KINDS.contains(&r.kind.as_str()) && seen.insert(r.symbol.clone()))
+        .map(|r| {
+            let mut candidate = TickerCandidate {
+                matched_isin: isin_symbols.contains(&r.symbol),
+                symbol: r.symbol,
+                name: r.name,
+                exchange: r.exchange,
+                kind: r.kind,
+                currency: None,
+                score: 0,
+            };
+            candidate.score = candidate.compute_score(security_name);
+            candidate
         })
         .collect();
+    candidates.sort_by_key(|c| Reverse(c.score));
 
-    if !lse_candidates.is_empty() {
-        let symbol = lse_candidates[0].symbol.to_string();
-        if symbol != isin {
-            return Ok(Some(symbol));
+    // Currency needs one rate-limited lookup per symbol, so only the leading candidates
+    // close enough to the best for it to change the order get one
+    let best = candidates.first().map(|c| c.score).unwrap_or_default();
+    let mut currencies = HashMap::new();
+    for c in candidates.iter().take_while(|c| c.score + STERLING_SCORE >= best).take(CURRENCY_LOOKUPS) {
+        if let Ok(Some(currency)) = source.currency(&c.symbol).await {
+            currencies.insert(c.symbol.clone(), currency);
         }
     }
+    for c in &mut candidates {
+        c.currency = currencies.remove(&c.symbol);
+        c.score = c.compute_score(security_name);
+    }
+    // Stable sort keeps the provider's order between equal scores
+    candidates.sort_by_key(|c| Reverse(c.score));
+    Ok(candidates)
+}
 
-    // Fallback to any valid quote if no LSE candidate
-    for r in response.results {
-        let symbol = r.symbol.to_string();
-        let kind_str = format!("{:?}", r.kind);
-        if symbol != isin && (kind_str == "Etf" || kind_str == "Equity" || kind_str == "MutualFund" || kind_str == "Currency") {
-            return Ok(Some(symbol));
+async fn perform_search(source: &dyn PriceSource, query: &str) -> Result<Vec<SymbolMatch>> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    source.search(query).await
+}
+
+impl TickerCandidate {
+    /// Score out of 100: ISIN match 35, fund or equity kind 15, London listing 20,
+    /// sterling quote 10 (`STERLING_SCORE`) and up to 20 for name similarity with the statement's name.
+    pub fn compute_score(&self, security_name: &str) -> u32 {
+        let mut score = 0;
+        if self.matched_isin {
+            score += 35;
+        }
+        if matches!(self.kind.as_str(), "Etf" | "Equity" | "MutualFund") {
+            score += 15;
+        }
+        if self.exchange.as_deref().is_some_and(|e| e.contains("LSE")) || self.symbol.ends_with(".L") {
+            score += 20;
+        }
+        if self.currency.as_deref().is_some_and(|c| matches!(c, "GBP" | "GBp" | "GBX")) {
+            score += STERLING_SCORE;
+        }
+        if let Some(ref name) = self.name {
+            score += (name_similarity(name, security_name) * 20.0).round() as u32;
+        }
+        score
+    }
+}
+
+/// Share of distinct words the two names have in common (Jaccard index).
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let words = |s: &str| -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(symbol: &str, name: &str, exchange: &str, kind: &str, currency: Option<&str>, matched_isin: bool) -> TickerCandidate {
+        TickerCandidate {
+            symbol: symbol.to_string(),
+            name: Some(name.to_string()),
+            exchange: Some(exchange.to_string()),
+            kind: kind.to_string(),
+            currency: currency.map(str::to_string),
+            matched_isin,
+            score: 0,
         }
     }
 
-    Ok(None)
+    #[test]
+    fn test_compute_score() {
+        let name = "Vanguard FTSE All-World UCITS ETF";
+        let gbp_line = candidate("VWRP.L", "Vanguard FTSE All-World UCITS ETF", "LSE", "Etf", Some("GBP"), true);
+        let usd_line = candidate("VWRA.L", "Vanguard FTSE All-World UCITS ETF USD Acc", "LSE", "Etf", Some("USD"), true);
+        let xetra = candidate("VWCE.DE", "Vanguard FTSE All-World UCITS ETF", "GER", "Etf", Some("EUR"), true);
+
+        assert_eq!(gbp_line.compute_score(name), 100);
+        let usd = usd_line.compute_score(name);
+        let eur = xetra.compute_score(name);
+        assert!(usd < 100 && eur < usd);
+
+        // Found by name only and not a fund
+        let index = candidate("^FTAW", "FTSE All-World", "FGI", "Index", None, false);
+        assert!(index.compute_score(name) < 20);
+    }
 }
@@ -0,0 +1,95 @@
+use crate::models::{TradingRecord, TransactionKind};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Where the prices used to value a ticker came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValuationSource {
+    Provider,
+    /// Manual prices only; the provider had nothing
+    Manual,
+    /// Approximate: execution prices from the statements, carried forward between trades
+    TradePrice,
+}
+
+/// Builds a daily price series in the statement currency from our own trades.
+///
+/// Each trading day gets the quantity-weighted average execution price of its buys, sells
+/// and reinvestments; transfers are skipped as their prices are book costs. Every day up to
+/// `end` without a trade carries the previous price forward.
+pub fn trade_price_series<'a>(trades: impl IntoIterator<Item = &'a TradingRecord>, end: NaiveDate) -> Vec<(NaiveDate, Decimal)> {
+    let mut by_date: BTreeMap<NaiveDate, (Decimal, Decimal)> = BTreeMap::new();
+    for t in trades {
+        let priced = matches!(
+            t.transaction_type,
+            TransactionKind::Buy | TransactionKind::Sell | TransactionKind::DividendReinvestment
+        );
+        if !priced || t.quantity <= Decimal::ZERO || t.share_price <= Decimal::ZERO {
+            continue;
+        }
+        let (value, quantity) = by_date.entry(t.trade_date_time.date()).or_default();
+        *value += t.share_price * t.quantity;
+        *quantity += t.quantity;
+    }
+
+    let mut series = Vec::new();
+    let mut points = by_date.into_iter().peekable();
+    let mut current = None;
+    let Some(&(first, _)) = points.peek() else {
+        return series;
+    };
+    for date in first.iter_days().take_while(|d| *d <= end) {
+        if let Some((_, (value, quantity))) = points.next_if(|(d, _)| *d == date) {
+            current = Some(value / quantity);
+        }
+        if let Some(price) = current {
+            series.push((date, price));
+        }
+    }
+    series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AccountType;
+    use rust_decimal_macros::dec;
+
+    fn trade(kind: TransactionKind, day: u32, quantity: Decimal, price: Decimal) -> TradingRecord {
+        let dt = NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        TradingRecord {
+            security_isin: "GB00B4PQW151".to_string(),
+            transaction_type: kind,
+            quantity,
+            share_price: price,
+            total_trade_value: quantity * price,
+            trade_date_time: dt,
+            settlement_date: dt,
+            broker: "Winterflood".to_string(),
+            account_type: AccountType::ISA,
+            ticker: None,
+        }
+    }
+
+    #[test]
+    fn test_trade_price_series_carries_forward() {
+        let trades = vec![
+            trade(TransactionKind::Buy, 2, dec!(10), dec!(100)),
+            trade(TransactionKind::Buy, 2, dec!(30), dec!(104)),
+            // Book cost, not a market price
+            trade(TransactionKind::TransferIn, 3, dec!(5), dec!(50)),
+            trade(TransactionKind::Sell, 5, dec!(20), dec!(110)),
+        ];
+        let end = NaiveDate::from_ymd_opt(2024, 1, 6).unwrap();
+        let series = trade_price_series(&trades, end);
+
+        let prices: Vec<Decimal> = series.iter().map(|(_, p)| *p).collect();
+        assert_eq!(series[0].0, NaiveDate::from_ymd_opt(2024, 1, 2).unwrap());
+        assert_eq!(prices, vec![dec!(103), dec!(103), dec!(103), dec!(110), dec!(110)]);
+
+        assert!(trade_price_series(&[], end).is_empty());
+    }
+}
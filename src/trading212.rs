@@ -0,0 +1,93 @@
+use crate::merge_csv::{ParseIssue, StatementInfo};
+use crate::models::TransactionKind;
+use crate::statement_parser::{
+    detect_by_header, for_each_row, normalised_cash, normalised_trade, BrokerRow,
+    ParsedStatement, StatementParser,
+};
+use rust_decimal::Decimal;
+
+const BROKER: &str = "Trading 212";
+const REQUIRED_COLUMNS: &[&str] = &["Action", "Time", "ISIN", "No. of shares", "Price / share", "Total"];
+const TIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M:%S"];
+
+/// Trading 212 "History" export: one row per order, deposit, dividend or interest payment.
+pub struct Trading212Parser;
+
+impl StatementParser for Trading212Parser {
+    fn broker(&self) -> &'static str {
+        BROKER
+    }
+
+    fn detect(&self, filename: &str, content: &str) -> Option<StatementInfo> {
+        detect_by_header(filename, content, REQUIRED_COLUMNS)
+    }
+
+    fn parse(&self, filename: &str, content: &str, info: &StatementInfo) -> ParsedStatement {
+        let account = info.account_type;
+        let mut trades = Vec::new();
+        let mut cash = Vec::new();
+
+        let issues = for_each_row(filename, content, |row| {
+            let action = row.get("Action");
+            let time = row.datetime("Time", TIME_FORMATS)?;
+            let lower = action.to_lowercase();
+
+            if lower.ends_with(" buy") || lower.ends_with(" sell") {
+                let is_buy = lower.ends_with(" buy");
+                let quantity = row.decimal("No. of shares")?;
+                let total = row.decimal("Total")?.abs();
+                let share_price = share_price_in_gbp(row, quantity, total)?;
+                let name = match row.get("Name") {
+                    "" => row.get("Ticker"),
+                    name => name,
+                };
+                trades.push(normalised_trade(
+                    name,
+                    row.get("ISIN"),
+                    if is_buy { TransactionKind::Buy } else { TransactionKind::Sell },
+                    quantity,
+                    share_price,
+                    total,
+                    time,
+                    BROKER,
+                    account,
+                ));
+                let activity = format!("{}: {}", if is_buy { "Buy" } else { "Sell" }, row.get("Ticker"));
+                cash.push(normalised_cash(time, &activity, if is_buy { -total } else { total }, account));
+            } else if lower == "deposit" {
+                cash.push(normalised_cash(time, "Payment Received", row.decimal("Total")?.abs(), account));
+            } else if lower == "withdrawal" {
+                cash.push(normalised_cash(time, "Withdrawal", -row.decimal("Total")?.abs(), account));
+            } else if lower.starts_with("dividend") {
+                let activity = format!("Dividend: {}", row.get("Ticker"));
+                cash.push(normalised_cash(time, &activity, row.decimal("Total")?, account));
+            } else if lower.contains("interest") {
+                cash.push(normalised_cash(time, "Interest", row.decimal("Total")?, account));
+            } else if lower != "currency conversion" {
+                // Conversions move money between currency sub-balances and carry no GBP flow
+                return Err(row.issue("Action", "Unsupported Trading 212 action"));
+            }
+            Ok(())
+        });
+
+        cash.sort_by_key(|c| c.date);
+        ParsedStatement { trades, cash, issues }
+    }
+}
+
+/// Converts the quoted price into GBP per share.
+///
+/// "Exchange rate" is instrument currency per GBP; when it is missing the price is
+/// derived from the GBP total, which includes any FX fee.
+fn share_price_in_gbp(row: &BrokerRow, quantity: Decimal, total: Decimal) -> Result<Decimal, ParseIssue> {
+    let price = row.decimal("Price / share")?;
+    match row.get("Currency (Price / share)") {
+        "" | "GBP" => Ok(price),
+        "GBX" => Ok(price / Decimal::ONE_HUNDRED),
+        _ => match row.optional_decimal("Exchange rate")? {
+            Some(rate) if !rate.is_zero() => Ok(price / rate),
+            _ if !quantity.is_zero() => Ok(total / quantity),
+            _ => Err(row.issue("Exchange rate", "Cannot convert share price to GBP")),
+        },
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+use investengine_csv_server_rs::background_processor::precompute_portfolio_data;
+use investengine_csv_server_rs::csv_prices::CsvPriceSource;
+use investengine_csv_server_rs::database::Database;
+use investengine_csv_server_rs::models::{AccountType, TradingRecord, TransactionKind};
+use investengine_csv_server_rs::price_source::PriceSource;
+use investengine_csv_server_rs::tickers::{rank_ticker_candidates, search_ticker_for_isin};
+use rust_decimal_macros::dec;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn csv_source() -> CsvPriceSource {
+    CsvPriceSource::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/prices"))
+}
+
+fn d(y: i32, m: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, day).unwrap()
+}
+
+#[tokio::test]
+async fn test_csv_price_source() {
+    let source = csv_source();
+
+    let prices = source.historical_prices("VWRP.L", d(2024, 1, 3), d(2024, 1, 5)).await.unwrap();
+    assert_eq!(prices.len(), 3);
+    assert_eq!(prices[0], (d(2024, 1, 3), dec!(100.50), "GBP".to_string()));
+
+    // No Currency column means GBP
+    let fx = source.historical_prices("GBPUSD=X", d(2024, 1, 1), d(2024, 1, 31)).await.unwrap();
+    assert_eq!(fx.len(), 14);
+    assert_eq!(source.currency("AAPL").await.unwrap().as_deref(), Some("USD"));
+    assert_eq!(source.currency("MISSING").await.unwrap(), None);
+    assert!(source.historical_prices("MISSING", d(2024, 1, 1), d(2024, 1, 31)).await.is_err());
+
+    // Symbols cannot reach files outside the directory
+    assert_eq!(source.currency("../prices/AAPL").await.unwrap(), None);
+    assert!(source.historical_prices("..\\prices\\AAPL", d(2024, 1, 1), d(2024, 1, 31)).await.is_err());
+
+    let by_name = source.search("all-world").await.unwrap();
+    assert_eq!(by_name.len(), 3);
+    assert_eq!(by_name[0].symbol, "VWRP.L");
+
+    let symbol = search_ticker_for_isin(&source, "", "US0378331005").await.unwrap();
+    assert_eq!(symbol.as_deref(), Some("AAPL"));
+}
+
+#[tokio::test]
+async fn test_rank_ticker_candidates() {
+    let source = csv_source();
+
+    // Three listings share the ISIN; the sterling London line wins
+    let candidates = rank_ticker_candidates(&source, "Vanguard FTSE All-World UCITS ETF", "IE00BK5BQT80").await.unwrap();
+    let symbols: Vec<&str> = candidates.iter().map(|c| c.symbol.as_str()).collect();
+    assert_eq!(symbols, vec!["VWRP.L", "VWRA.L", "VWCE.DE"]);
+    assert_eq!(candidates[0].currency.as_deref(), Some("GBP"));
+    assert_eq!(candidates[1].currency.as_deref(), Some("USD"));
+    // Too far behind for a sterling quote to lift it, so its currency is not looked up
+    assert_eq!(candidates[2].currency, None);
+    assert!(candidates.iter().all(|c| c.matched_isin));
+    assert!(candidates[0].score > candidates[1].score);
+
+    let symbol = search_ticker_for_isin(&source, "", "IE00BK5BQT80").await.unwrap();
+    assert_eq!(symbol.as_deref(), Some("VWRP.L"));
+}
+
+#[tokio::test]
+async fn test_precompute_with_csv_prices() {
+    let mongo_uri = std::env::var("TEST_MONGO_URI")
+        .unwrap_or_else(|_| "mongodb://localhost:27017/test_investengine_prices?serverSelectionTimeoutMS=2000".to_string());
+
+    let db = match Database::new(&mongo_uri).await {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            println!("Skipping test: MongoDB not available: {}", e);
+            return;
+        }
+    };
+    db.reset().await.expect("Failed to reset test database");
+
+    let dt = d(2024, 1, 3).and_hms_opt(10, 0, 0).unwrap();
+    let trade = TradingRecord {
+        security_isin: "IE00BK5BQT80".to_string(),
+        transaction_type: TransactionKind::Buy,
+        quantity: dec!(10),
+        share_price: dec!(100.50),
+        total_trade_value: dec!(1005),
+        trade_date_time: dt,
+        settlement_date: dt,
+        broker: "Winterflood".to_string(),
+        account_type: AccountType::ISA,
+        ticker: Some("VWRP.L".to_string()),
+    };
+    db.save_trades(&[trade]).await.expect("Failed to save trade");
+
+    let source: Arc<dyn PriceSource> = Arc::new(csv_source());
+    precompute_portfolio_data(Arc::clone(&db), source).await.expect("Precompute failed");
+
+    let data = db.get_portfolio_values_precomputed(None).await.unwrap().expect("No precomputed values");
+    let values = data["daily_values"].as_array().expect("daily_values missing");
+    // 10 units at the 2024-01-05 close of 101.50
+    assert_eq!(values[2].as_f64(), Some(1015.0));
+}
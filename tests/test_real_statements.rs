@@ -14,7 +14,7 @@ async fn test_merge_real_trading_statements() {
     for path in paths {
         let full_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path);
         if full_path.exists() {
-            let content = fs::read_to_string(&full_path).expect(&format!("Failed to read {}", path));
+            let content = fs::read_to_string(&full_path).unwrap_or_else(|_| panic!("Failed to read {}", path));
             let filename = full_path.file_name().unwrap().to_str().unwrap().to_string();
             file_data.push((filename, content));
         }
@@ -65,7 +65,7 @@ async fn test_merge_real_cash_statements() {
     for path in paths {
         let full_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path);
         if full_path.exists() {
-            let content = fs::read_to_string(&full_path).expect(&format!("Failed to read {}", path));
+            let content = fs::read_to_string(&full_path).unwrap_or_else(|_| panic!("Failed to read {}", path));
             let filename = full_path.file_name().unwrap().to_str().unwrap().to_string();
             file_data.push((filename, content));
         }
@@ -0,0 +1,113 @@
+use investengine_csv_server_rs::merge_csv::{DetectionConfidence, FileType};
+use investengine_csv_server_rs::models::{AccountType, TransactionKind};
+use investengine_csv_server_rs::security_parser::extract_security_and_isin;
+use investengine_csv_server_rs::statement_parser::ParserRegistry;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::fs;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> (String, String) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    let content = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read {}", name));
+    (name.to_string(), content)
+}
+
+#[test]
+fn test_trading212_export() {
+    let registry = ParserRegistry::default();
+    let (filename, content) = fixture("ISA_trading212_history.csv");
+    let (broker, info, parsed) = registry.parse(&filename, &content).expect("file should be recognised");
+
+    assert_eq!(broker, "Trading 212");
+    assert_eq!(info.file_type, FileType::Combined);
+    assert_eq!(info.account_type, AccountType::ISA);
+    assert_eq!(info.confidence, DetectionConfidence::High);
+    assert!(parsed.issues.is_empty(), "unexpected issues: {:?}", parsed.issues);
+
+    assert_eq!(parsed.trades.len(), 3);
+    let vusa = &parsed.trades[0];
+    assert_eq!(extract_security_and_isin(&vusa.security_isin).1.as_deref(), Some("IE00B3XXRP09"));
+    assert_eq!(vusa.transaction_type, TransactionKind::Buy);
+    // Quoted in pence, stored in pounds
+    assert_eq!(vusa.share_price, dec!(75.50));
+    assert_eq!(vusa.total_trade_value, dec!(377.50));
+    assert_eq!(vusa.broker, "Trading 212");
+    assert_eq!(parsed.trades[2].transaction_type, TransactionKind::Sell);
+    assert_eq!(parsed.trades[2].share_price, dec!(136));
+
+    // Deposit, two buys, dividend, interest, sell, withdrawal; the FX conversion carries no flow
+    assert_eq!(parsed.cash.len(), 7);
+    assert_eq!(parsed.cash[0].activity, "Payment Received");
+    assert_eq!(parsed.cash.last().unwrap().activity, "Withdrawal");
+    // The export has no balance column, so none is made up
+    assert!(parsed.cash.iter().all(|c| c.balance.is_none()));
+    assert_eq!(parsed.cash.iter().map(|c| c.net_flow).sum::<Decimal>(), dec!(268.87));
+}
+
+#[test]
+fn test_freetrade_export() {
+    let registry = ParserRegistry::default();
+    let (filename, content) = fixture("GIA_freetrade_activity.csv");
+    let (broker, info, parsed) = registry.parse(&filename, &content).expect("file should be recognised");
+
+    assert_eq!(broker, "Freetrade");
+    assert_eq!(info.account_type, AccountType::GIA);
+    assert!(parsed.issues.is_empty(), "unexpected issues: {:?}", parsed.issues);
+
+    assert_eq!(parsed.trades.len(), 3);
+    assert_eq!(parsed.trades[0].quantity, dec!(4));
+    assert_eq!(parsed.trades[0].share_price, dec!(100));
+    assert_eq!(parsed.trades[2].transaction_type, TransactionKind::Sell);
+
+    assert_eq!(parsed.cash.len(), 7);
+    assert_eq!(parsed.cash.iter().filter(|c| c.activity == "Payment Received").count(), 1);
+    assert_eq!(parsed.cash.last().unwrap().net_flow, dec!(-100));
+    assert!(parsed.cash.iter().all(|c| c.balance.is_none()));
+    assert_eq!(parsed.cash.iter().map(|c| c.net_flow).sum::<Decimal>(), dec!(130.47));
+}
+
+#[test]
+fn test_exports_with_byte_order_mark() {
+    let registry = ParserRegistry::default();
+    for name in ["ISA_trading212_history.csv", "GIA_freetrade_activity.csv"] {
+        let (filename, content) = fixture(name);
+        let (_, _, parsed) = registry.parse(&filename, &format!("\u{feff}{}", content)).expect("file should be recognised");
+        assert!(parsed.issues.is_empty(), "unexpected issues in {}: {:?}", name, parsed.issues);
+        assert_eq!(parsed.trades.len(), 3);
+        assert_eq!(parsed.cash.len(), 7);
+    }
+}
+
+#[test]
+fn test_unsupported_rows_become_issues() {
+    let registry = ParserRegistry::default();
+    let content = "Action,Time,ISIN,Ticker,Name,No. of shares,Price / share,Currency (Price / share),Exchange rate,Total\n\
+Deposit,2024-01-02 09:15:04,,,,,,,,100.00\n\
+Stock split open,2024-01-05 00:00:00,US0378331005,AAPL,Apple,4,50,USD,1.27,0\n\
+Market buy,not a date,US0378331005,AAPL,Apple,1,185,USD,1.27,145.67\n";
+    let (broker, info, parsed) = registry.parse("history.csv", content).unwrap();
+
+    assert_eq!(broker, "Trading 212");
+    // No account in the filename, so the header alone cannot make this high confidence
+    assert_eq!(info.confidence, DetectionConfidence::Medium);
+    assert_eq!(parsed.cash.len(), 1);
+    assert_eq!(parsed.issues.len(), 2);
+    assert_eq!(parsed.issues[0].line, 3);
+    assert_eq!(parsed.issues[0].column.as_deref(), Some("Action"));
+    assert_eq!(parsed.issues[1].column.as_deref(), Some("Time"));
+}
+
+#[test]
+fn test_investengine_remains_the_default() {
+    let registry = ParserRegistry::default();
+    let content = "Transaction Statement: ISA\n\
+Security / ISIN,Transaction Type,Quantity,Share Price,Total Trade Value,Trade Date/Time,Settlement Date,Broker\n\
+Vanguard FTSE All-World / ISIN IE00BK5BQT80,Buy,2,£100.00,£200.00,05/06/22 10:00:00,07/06/22,Winterflood\n";
+    let (broker, info, parsed) = registry.parse("statement.csv", content).unwrap();
+
+    assert_eq!(broker, "InvestEngine");
+    assert_eq!(info.file_type, FileType::Trading);
+    assert_eq!(parsed.trades.len(), 1);
+    assert!(parsed.cash.is_empty());
+}